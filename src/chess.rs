@@ -4,12 +4,44 @@ pub mod movement;
 pub mod checker;
 pub mod keeper;
 pub mod interpret;
+pub mod render;
+pub mod san;
+pub mod eval;
+pub mod engine;
+pub mod fen;
+pub mod game;
+pub mod pgn;
+pub mod net;
+pub mod broadcast;
+pub mod api;
+pub mod correspondence;
+pub mod zobrist;
+pub mod book;
+pub mod eco;
+pub mod dtm;
+pub mod puzzle;
+pub mod review;
+pub mod tactics;
+pub mod see;
+pub mod control;
+pub mod pawns;
+pub mod uci;
+pub mod tt;
+pub mod ponder;
+pub mod timeman;
+#[cfg(feature = "serde")]
+pub mod serialize;
 
+use anyhow::{anyhow, Result};
 use colored::*;
+use std::time::Instant;
 use self::board::Board;
+use self::game::{format_clock, Game, AUTOSAVE_FILE};
+use self::render::{ControlView, Glyphs, Orientation, RenderOptions, Theme, THEME_NAMES};
+use self::ponder::Ponder;
+use self::tt::TranspositionTable;
 use self::utils::Player;
 use crate::chess::checker::Snapshot;
-use crate::chess::movement::castle;
 use crate::input::get_input;
 
 pub enum LoopState {
@@ -17,27 +49,145 @@ pub enum LoopState {
     Exit,
 }
 
-pub fn run(board: &mut Board) -> LoopState {
+pub fn run(game: &mut Game, options: &mut RenderOptions) -> LoopState {
     clearscr!();
 
-    board.print();
+    let mut view = *options;
+    if options.mark_hanging {
+        view.hanging = see::hanging_squares(&game.board);
+    }
+    game.board.print_with(&view);
+    println!(
+        "{} {}    {} {}",
+        game.white_name.bold(),
+        format_clock(game.white_clock),
+        game.black_name.green(),
+        format_clock(game.black_clock),
+    );
+    if let Some(opening) = eco::classify(&game.board) {
+        println!("{} {}", opening.code.bold(), opening.name);
+    }
+    println!();
 
-    let snap = Snapshot::new(board);
-    if checker::is_in_check(&snap, board.get_turn()) {
+    if let Some(broadcast) = &game.broadcast {
+        broadcast.set_players(&game.white_name, &game.black_name);
+        broadcast.update(&game.board, Some((game.white_clock, game.black_clock)));
+    }
+
+    let turn = game.board.get_turn();
+
+    if let Some(outcome) = checker::outcome(&game.board) {
+        if let Some(broadcast) = &game.broadcast {
+            let reason = match outcome {
+                checker::Outcome::Checkmate(_) => "checkmate",
+                checker::Outcome::Stalemate => "stalemate",
+            };
+            broadcast.finish(&format!("{} {}", outcome.result(), reason));
+        }
+        match outcome {
+            checker::Outcome::Checkmate(Player::White) => println!("Checkmate! {} wins.", game.white_name.bold()),
+            checker::Outcome::Checkmate(Player::Black) => println!("Checkmate! {} wins.", game.black_name.green().bold()),
+            checker::Outcome::Stalemate => println!("Stalemate! The game is a draw."),
+        }
+        println!("Press enter to exit...");
+
+        #[allow(unused_variables)]
+        let input = get_input();
+
+        return LoopState::Exit;
+    }
+
+    if let Some(opponent) = game.opponent {
+        if std::mem::discriminant(&opponent.side) == std::mem::discriminant(&turn) {
+            println!("Thinking...");
+            let started = Instant::now();
+            let pondered = game.pondering.take().and_then(|ponder| ponder.finish(&game.board));
+            let chosen = engine::choose_move(&game.board, opponent.depth, game.book.as_ref(), pondered);
+            *game.clock_mut(turn) += started.elapsed();
+
+            if let Some((m, reply)) = chosen {
+                if game.board.play(m).is_ok() {
+                    game.board.next_turn();
+                    if opponent.ponder {
+                        game.pondering = reply.and_then(|reply| Ponder::start(&game.board, reply, opponent.depth));
+                    }
+                }
+            }
+            return LoopState::Continue;
+        }
+    }
+
+    if game.analysis_lines > 0 {
+        show_analysis(&game.board, game.analysis_lines);
+    }
+
+    let snap = Snapshot::new(&game.board);
+    if checker::is_in_check(&snap, turn) {
         println!("C H E C K !");
     }
 
-    match board.get_turn() {
-        Player::White => println!("{}, make your move!", "White".bold()),
-        Player::Black => println!("{}, make your move!", "Black".green().bold()),
+    match turn {
+        Player::White => println!("{}, make your move!", game.white_name.bold()),
+        Player::Black => println!("{}, make your move!", game.black_name.green().bold()),
+    }
+
+    let started = Instant::now();
+    let raw = get_input();
+    *game.clock_mut(turn) += started.elapsed();
+
+    let raw = raw.trim();
+    let input = raw.to_lowercase();
+    let input = input.as_str();
+
+    if input == "q" {
+        if let Some(broadcast) = &game.broadcast {
+            broadcast.finish("* game stopped");
+        }
+        match game.save(AUTOSAVE_FILE) {
+            Ok(()) => println!("Game saved to {}.", AUTOSAVE_FILE),
+            Err(error) => println!("{}", error),
+        }
+        return LoopState::Exit;
+    }
+
+    if let Some(result) = game_command(raw, game) {
+        match result {
+            Ok(message) => println!("{} Press enter to continue...", message),
+            Err(error) => println!("{:#} Press enter to continue...", error),
+        }
+
+        #[allow(unused_variables)]
+        let input = get_input();
+
+        return LoopState::Continue;
     }
 
-    let input = get_input().to_lowercase();
-    let input = input.trim();
+    let board = &mut game.board;
+
+    if let Some(result) = render_command(input, options, board.get_turn()) {
+        if let Err(error) = result {
+            println!("{} Press enter to continue...", error);
 
-    if input == "q" { return LoopState::Exit; }
+            #[allow(unused_variables)]
+            let input = get_input();
+        }
 
-    match interpret::interpret_notation(input, board) {
+        return LoopState::Continue;
+    }
+
+    if let Some(result) = query_command(input, board, options) {
+        if let Err(error) = result {
+            println!("{}", error);
+        }
+        println!("Press enter to continue...");
+
+        #[allow(unused_variables)]
+        let input = get_input();
+
+        return LoopState::Continue;
+    }
+
+    match interpret::interpret_notation(raw, board) {
         Err(error) => {
             println!("{} Press enter to continue...", error);
 
@@ -46,66 +196,269 @@ pub fn run(board: &mut Board) -> LoopState {
 
             return LoopState::Continue;
         },
-        Ok(m) => {
-            match m.castle {
-                None => {
-                    if let Err(error) = board.play(m) {
-                        println!("{}", error);
+        Ok(mut m) => {
+            if m.is_promotion(&board.get_spaces()) && m.promotion.is_none() {
+                m.promotion = Some(choose_promotion(m.to));
+            }
 
-                        #[allow(unused_variables)]
-                        let input = get_input();
+            if let Err(error) = board.play(m) {
+                println!("{}", error);
 
-                        return LoopState::Continue;
-                    };
-                }
-                Some(castle_options) => {
-                    if let Err(error) = castle(board, castle_options, board.get_turn()) {
-                        println!("{}", error);
+                #[allow(unused_variables)]
+                let input = get_input();
 
-                        #[allow(unused_variables)]
-                        let input = get_input();
+                return LoopState::Continue;
+            };
+        }
+    }
 
-                        return LoopState::Continue;
-                    }
-                }
-            }
+    board.next_turn();
+
+    LoopState::Continue
+}
+
+fn choose_promotion(pawn: usize) -> utils::PieceType {
+    loop {
+        println!("Pawn moving to {} is able to be promoted! Choose your promotion: Q, B, N, R", san::square_name(pawn));
+        let input = get_input().to_lowercase();
+        match input.trim() {
+            "q" => { return utils::PieceType::Queen; },
+            "b" => { return utils::PieceType::Bishop; },
+            "n" => { return utils::PieceType::Knight; },
+            "r" => { return utils::PieceType::Rook; },
+            _ => { },
         }
     }
+}
 
-    let snap = Snapshot::new(board);
-    let promotable_pawns = checker::pawns_to_promote(&snap);
-    if !promotable_pawns.is_empty() {
-        for pawn in promotable_pawns {
-            loop {
-                println!("Pawn at {} is able to be promoted! Choose your promotion: Q, B, N, R", pawn);
-                let input = get_input().to_lowercase();
-                let input = input.trim();
-                let player = board.get_space(pawn).unwrap().extract_player().unwrap();
-                match input {
-                    "q" => {
-                        board.change_piece(pawn, utils::Piece::Piece(utils::PlayerPiece { piece: utils::PieceType::Queen, player  }));
-                        break;
-                    }
-                    "b" => {
-                        board.change_piece(pawn, utils::Piece::Piece(utils::PlayerPiece { piece: utils::PieceType::Bishop, player  }));
-                        break;
-                    }
-                    "n" => {
-                        board.change_piece(pawn, utils::Piece::Piece(utils::PlayerPiece { piece: utils::PieceType::Knight, player  }));
-                        break;
-                    }
-                    "r" => {
-                        board.change_piece(pawn, utils::Piece::Piece(utils::PlayerPiece { piece: utils::PieceType::Rook, player  }));
-                        break;
-                    }
-                    _ => { }
-                }
+/// Handles the commands that act on the whole game (`save`, `load`, `name`, `analysis`).
+/// Takes the input before it is lowercased, since file and player names keep their case.
+/// Returns `None` if the input is not a game command.
+fn game_command(raw: &str, game: &mut Game) -> Option<Result<String>> {
+    let (command, rest) = match raw.split_once(' ') {
+        Some((command, rest)) => (command.to_lowercase(), rest.trim()),
+        None => (raw.to_lowercase(), ""),
+    };
+
+    let result = match command.as_str() {
+        "save" if rest.is_empty() => Err(anyhow!("Usage: save <file>")),
+        "save" => game.save(rest).map(|_| format!("Game saved to {}.", rest)),
+        "load" if rest.is_empty() => Err(anyhow!("Usage: load <file>")),
+        "load" => Game::load(rest).map(|mut loaded| {
+            loaded.opponent = game.opponent;
+            loaded.broadcast = game.broadcast.take();
+            loaded.book = game.book.take();
+            loaded.analysis_lines = game.analysis_lines;
+            *game = loaded;
+            format!("Game loaded from {}.", rest)
+        }),
+        "name" => {
+            match rest.split_once(' ') {
+                Some((side, name)) if side.eq_ignore_ascii_case("white") => {
+                    game.white_name = name.trim().to_string();
+                    Ok(format!("White is now {}.", game.white_name))
+                },
+                Some((side, name)) if side.eq_ignore_ascii_case("black") => {
+                    game.black_name = name.trim().to_string();
+                    Ok(format!("Black is now {}.", game.black_name))
+                },
+                _ => Err(anyhow!("Usage: name white|black <name>")),
             }
+        },
+        "analysis" => match rest {
+            "" => {
+                game.analysis_lines = if game.analysis_lines == 0 { ANALYSIS_LINES } else { 0 };
+                Ok(if game.analysis_lines == 0 { "Analysis is off.".to_string() } else { "Analysis is on.".to_string() })
+            },
+            "off" => {
+                game.analysis_lines = 0;
+                Ok("Analysis is off.".to_string())
+            },
+            lines => match lines.parse::<usize>() {
+                Ok(n) if n > 0 => {
+                    game.analysis_lines = n;
+                    Ok(format!("Showing the {} best lines.", n))
+                },
+                _ => Err(anyhow!("Usage: analysis [N|off]")),
+            },
+        },
+        "ponder" => match (game.opponent.as_mut(), rest) {
+            (None, _) => Err(anyhow!("There is no computer player to ponder.")),
+            (Some(opponent), "on") => {
+                opponent.ponder = true;
+                Ok("The computer thinks on your time.".to_string())
+            },
+            (Some(opponent), "off") => {
+                opponent.ponder = false;
+                game.pondering = None;
+                Ok("The computer no longer thinks on your time.".to_string())
+            },
+            _ => Err(anyhow!("Usage: ponder on|off")),
+        },
+        _ => { return None; },
+    };
+
+    Some(result)
+}
+
+/// Depth searched when the player asks for a hint.
+const HINT_DEPTH: u32 = 3;
+
+/// Lines shown when analysis is turned on without a count, and the depth they are searched to.
+const ANALYSIS_LINES: usize = 3;
+const ANALYSIS_DEPTH: u32 = 3;
+
+/// Handles the commands that answer questions about the position (`moves`, `attackers`, `hint`,
+/// `tactics`).
+/// Returns `None` if the input is not a query.
+fn query_command(input: &str, board: &Board, options: &RenderOptions) -> Option<Result<()>> {
+    let args: Vec<_> = input.split_whitespace().collect();
+
+    let result = match args.as_slice() {
+        ["moves", square] => show_moves(square, board, options),
+        ["moves", ..] => Err(anyhow!("Usage: moves <square>, e.g. moves e2")),
+        ["attackers", square] => show_attackers(square, board),
+        ["attackers", ..] => Err(anyhow!("Usage: attackers <square>, e.g. attackers d5")),
+        ["tactics"] => {
+            show_tactics(board);
+            Ok(())
+        },
+        ["hint"] => {
+            let result = engine::search(board, HINT_DEPTH);
+            match result.best_move {
+                None => Err(anyhow!("There are no legal moves!")),
+                Some(m) => {
+                    println!("Hint: {} ({})", san::to_san(board, &m), engine::format_score(result.score));
+                    Ok(())
+                },
+            }
+        },
+        _ => { return None; },
+    };
+
+    Some(result)
+}
+
+fn show_moves(square: &str, board: &Board, options: &RenderOptions) -> Result<()> {
+    let from = interpret::parse_square(square)?;
+    let piece = match board.get_space(from) {
+        Some(utils::Piece::Piece(p)) => p,
+        _ => { return Err(anyhow!("There is no piece on {}!", square)); },
+    };
+    if std::mem::discriminant(&piece.player) != std::mem::discriminant(&board.get_turn()) {
+        return Err(anyhow!("The piece on {} is not yours!", square));
+    }
+
+    let moves: Vec<_> = movement::legal_moves(board).into_iter().filter(|m| m.from == from).collect();
+
+    let mut view = *options;
+    for m in &moves {
+        view.highlights[m.to] = true;
+    }
+    clearscr!();
+    board.print_with(&view);
+
+    if moves.is_empty() {
+        let snap = Snapshot::new(board);
+        if checker::is_in_check(&snap, board.get_turn()) {
+            println!("The {} on {} has no legal moves, you need to get out of check!", piece.to_char(), square);
+        } else {
+            println!("The {} on {} has no legal moves.", piece.to_char(), square);
         }
+        return Ok(());
     }
 
-    board.next_turn();
+    let names: Vec<_> = moves.iter().map(|m| san::to_san(board, m)).collect();
+    println!("Legal moves for the {} on {}: {}", piece.to_char(), square, names.join(", "));
+    Ok(())
+}
 
-    LoopState::Continue
+/// Prints the engine's best lines for the position, scores from White's side.
+fn show_analysis(board: &Board, lines: usize) {
+    let analysis = engine::search_lines(board, engine::Limits::depth(ANALYSIS_DEPTH), lines, 1, &TranspositionTable::default(), |_| { });
+    println!("Analysis, depth {}:", analysis.depth);
+    for (i, line) in analysis.lines.iter().enumerate() {
+        let score = match board.get_turn() {
+            Player::White => line.score,
+            Player::Black => -line.score,
+        };
+        println!("  {}. {:>6} {}", i + 1, engine::format_score(score), san::line_to_san(board, &line.pv));
+    }
+    println!();
+}
+
+fn show_tactics(board: &Board) {
+    let findings = tactics::find(board);
+    if findings.is_empty() {
+        println!("Nothing tactical stands out.");
+    }
+    for finding in findings {
+        let side = match finding.player {
+            Player::White => "White".bold(),
+            Player::Black => "Black".green(),
+        };
+        println!("{}: {}.", side, finding.describe(board));
+    }
 }
 
+fn show_attackers(square: &str, board: &Board) -> Result<()> {
+    let target = interpret::parse_square(square)?;
+    let snap = Snapshot::new(board);
+
+    for (player, name) in [(Player::White, "White".bold()), (Player::Black, "Black".green())] {
+        let found: Vec<_> = checker::attackers(&snap, target, player).iter()
+            .map(|&i| {
+                let piece = board.get_space(i).and_then(|p| p.extract_value()).map(|p| p.to_char()).unwrap_or('?');
+                format!("{}{}", piece, san::square_name(i))
+            })
+            .collect();
+        if found.is_empty() {
+            println!("{} does not attack {}.", name, square);
+        } else {
+            println!("{} attacks {} with: {}", name, square, found.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+/// Handles the display commands (`flip`, `view`, `glyphs`, `theme`, `compact`, `panel`, `hanging`,
+/// `control`).
+/// Returns `None` if the input is not a display command.
+fn render_command(input: &str, options: &mut RenderOptions, turn: Player) -> Option<Result<()>> {
+    let args: Vec<_> = input.split_whitespace().collect();
+
+    let result = match args.as_slice() {
+        ["flip"] => {
+            options.orientation = match options.bottom(turn) {
+                Player::White => Orientation::Black,
+                Player::Black => Orientation::White,
+            };
+            Ok(())
+        },
+        ["view", "white"] => { options.orientation = Orientation::White; Ok(()) },
+        ["view", "black"] => { options.orientation = Orientation::Black; Ok(()) },
+        ["view", "auto"] => { options.orientation = Orientation::SideToMove; Ok(()) },
+        ["view", ..] => Err(anyhow!("Usage: view white|black|auto")),
+        ["glyphs", "ascii"] => { options.glyphs = Glyphs::Ascii; Ok(()) },
+        ["glyphs", "unicode"] => { options.glyphs = Glyphs::Unicode; Ok(()) },
+        ["glyphs", ..] => Err(anyhow!("Usage: glyphs ascii|unicode")),
+        ["theme", name] => {
+            match Theme::from_name(name) {
+                Some(theme) => { options.theme = theme; Ok(()) },
+                None => Err(anyhow!("Unknown theme! Choose one of: {}", THEME_NAMES.join(", "))),
+            }
+        },
+        ["theme", ..] => Err(anyhow!("Usage: theme {}", THEME_NAMES.join("|"))),
+        ["compact"] => { options.compact = !options.compact; Ok(()) },
+        ["panel"] => { options.move_list = !options.move_list; Ok(()) },
+        ["hanging"] => { options.mark_hanging = !options.mark_hanging; Ok(()) },
+        ["control", "shade"] => { options.control_view = Some(ControlView::Shade); Ok(()) },
+        ["control", "counts"] => { options.control_view = Some(ControlView::Counts); Ok(()) },
+        ["control", "off"] => { options.control_view = None; Ok(()) },
+        ["control", ..] => Err(anyhow!("Usage: control shade|counts|off")),
+        _ => { return None; },
+    };
+
+    Some(result)
+}
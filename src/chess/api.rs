@@ -0,0 +1,398 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::Board,
+    checker::outcome,
+    fen::piece_to_char,
+    interpret::parse_uci,
+    movement::legal_moves,
+    pgn::PgnGame,
+    san::{parse_san, to_san, to_uci},
+    utils::{Piece, Player},
+};
+
+// Endpoints. Bodies are JSON objects with string values; every answer is JSON except the PGN.
+//
+//   POST   /games                 {"fen": "..."} optional   create a game
+//   GET    /games                                            list the games
+//   GET    /games/{id}                                       board, FEN, moves and result
+//   DELETE /games/{id}                                       forget a game
+//   GET    /games/{id}/moves                                 legal moves in UCI and SAN
+//   POST   /games/{id}/moves      {"move": "Nf3"}            play a move in SAN or UCI
+//   POST   /games/{id}/undo                                  take back the last move
+//   GET    /games/{id}/fen
+//   GET    /games/{id}/pgn
+
+pub const DEFAULT_API_PORT: u16 = 8080;
+
+const MAX_BODY: usize = 64 * 1024;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An HTTP answer: status code, content type and body.
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Self {
+        Self { status, content_type: "application/json", body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self::json(status, format!("{{\"error\":{}}}", json_string(message)))
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            201 => "Created",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            500 => "Internal Server Error",
+            _ => "Error",
+        }
+    }
+}
+
+/// The games the API knows about, by id.
+#[derive(Default)]
+struct Games {
+    next_id: u64,
+    boards: BTreeMap<u64, Board>,
+}
+
+/// An HTTP server for creating and playing games. Bind to port 0 to get a free port.
+pub struct ApiServer {
+    listener: TcpListener,
+    games: Arc<Mutex<Games>>,
+    read_timeout: Duration,
+}
+
+impl ApiServer {
+    pub fn bind(address: &str) -> Result<Self> {
+        let listener = TcpListener::bind(address).with_context(|| format!("Could not listen on {}", address))?;
+        Ok(Self { listener, games: Arc::new(Mutex::new(Games { next_id: 1, ..Games::default() })), read_timeout: READ_TIMEOUT })
+    }
+
+    /// Sets how long a client may take to send its request before it gets a 408.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Answers requests until the listener fails, one thread per connection.
+    pub fn serve(self) -> Result<()> {
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let games = Arc::clone(&self.games);
+            let read_timeout = self.read_timeout;
+            thread::spawn(move || {
+                let _ = handle_connection(stream, &games, read_timeout);
+            });
+        }
+        Ok(())
+    }
+
+    /// Runs the server on a background thread, for embedding in another program.
+    pub fn spawn(self) -> JoinHandle<Result<()>> {
+        thread::spawn(move || self.serve())
+    }
+
+    /// Answers a single request without going through a socket.
+    pub fn handle(&self, method: &str, path: &str, body: &str) -> Response {
+        route(&self.games, method, path, body)
+    }
+}
+
+fn handle_connection(stream: TcpStream, games: &Mutex<Games>, read_timeout: Duration) -> Result<()> {
+    stream.set_read_timeout(Some(read_timeout))?;
+    let response = match read_request(&stream, games) {
+        Ok(response) => response,
+        Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            Response::error(408, "The request took too long to arrive")
+        },
+        Err(error) => { return Err(error.into()); },
+    };
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len(),
+        response.body,
+    )?;
+    Ok(())
+}
+
+/// Reads one request from the stream and routes it.
+fn read_request(stream: &TcpStream, games: &Mutex<Games>) -> io::Result<Response> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("").to_string();
+    let path = parts.next().unwrap_or("").to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if content_length > MAX_BODY {
+        return Ok(Response::error(413, "The request body is too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(route(games, &method, &path, &String::from_utf8_lossy(&body)))
+}
+
+fn route(games: &Mutex<Games>, method: &str, path: &str, body: &str) -> Response {
+    // Games are only ever replaced whole, so a request that panicked left them as they were.
+    let mut games = games.lock().unwrap_or_else(PoisonError::into_inner);
+    let path = path.split('?').next().unwrap_or("");
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    match (method, segments.as_slice()) {
+        ("POST", ["games"]) => create_game(&mut games, body),
+        ("GET", ["games"]) => {
+            let ids: Vec<String> = games.boards.keys().map(|id| id.to_string()).collect();
+            Response::json(200, format!("{{\"games\":[{}]}}", ids.join(",")))
+        },
+        (_, ["games"]) => Response::error(405, "Use GET or POST"),
+        (_, ["games", id, rest @ ..]) => {
+            let id = match id.parse::<u64>() {
+                Ok(id) => id,
+                Err(_) => { return Response::error(404, "No such game"); },
+            };
+            if method == "DELETE" && rest.is_empty() {
+                return match games.boards.remove(&id) {
+                    Some(_) => Response::json(200, format!("{{\"deleted\":{}}}", id)),
+                    None => Response::error(404, "No such game"),
+                };
+            }
+            let board = match games.boards.get_mut(&id) {
+                Some(board) => board,
+                None => { return Response::error(404, "No such game"); },
+            };
+            game_route(id, board, method, rest, body)
+        },
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn game_route(id: u64, board: &mut Board, method: &str, rest: &[&str], body: &str) -> Response {
+    match (method, rest) {
+        ("GET", []) => Response::json(200, game_json(id, board)),
+        ("GET", ["moves"]) => {
+            let moves: Vec<String> = legal_moves(board).iter()
+                .map(|m| format!("{{\"uci\":{},\"san\":{}}}", json_string(&to_uci(m)), json_string(&to_san(board, m))))
+                .collect();
+            Response::json(200, format!("{{\"moves\":[{}]}}", moves.join(",")))
+        },
+        ("POST", ["moves"]) => {
+            let text = match json_field(body, "move") {
+                Some(text) => text,
+                None => { return Response::error(400, "Send {\"move\": \"<SAN or UCI>\"}"); },
+            };
+            if outcome(board).is_some() {
+                return Response::error(400, "The game is over");
+            }
+            let m = match parse_uci(text.trim(), board).or_else(|_| parse_san(text.trim(), board)) {
+                Ok(m) => m,
+                Err(error) => { return Response::error(400, &error.to_string()); },
+            };
+            let san = to_san(board, &m);
+            // Played on a copy, so the stored game only changes once the move went through.
+            let mut next = board.clone();
+            if let Err(error) = next.play(m) {
+                return Response::error(400, &error.to_string());
+            }
+            next.next_turn();
+            *board = next;
+            Response::json(200, format!(
+                "{{\"uci\":{},\"san\":{},\"fen\":{},\"result\":{}}}",
+                json_string(&to_uci(&m)),
+                json_string(&san),
+                json_string(&board.to_fen()),
+                json_string(result(board)),
+            ))
+        },
+        ("POST", ["undo"]) => {
+            match undo(board) {
+                Ok(previous) => {
+                    *board = previous;
+                    Response::json(200, game_json(id, board))
+                },
+                Err(error) => Response::error(400, &error.to_string()),
+            }
+        },
+        ("GET", ["fen"]) => Response::json(200, format!("{{\"fen\":{}}}", json_string(&board.to_fen()))),
+        ("GET", ["pgn"]) => Response { status: 200, content_type: "application/x-chess-pgn", body: PgnGame::new(board.clone()).to_pgn() },
+        (_, [] | ["moves"] | ["undo"] | ["fen"] | ["pgn"]) => Response::error(405, "Method not allowed"),
+        _ => Response::error(404, "Not found"),
+    }
+}
+
+fn create_game(games: &mut Games, body: &str) -> Response {
+    let board = match json_field(body, "fen") {
+        Some(fen) => match Board::from_fen(&fen) {
+            Ok(board) => board,
+            Err(error) => { return Response::error(400, &error.to_string()); },
+        },
+        None if body.trim().is_empty() || body.trim() == "{}" => Board::new(),
+        None => { return Response::error(400, "Send nothing, or {\"fen\": \"<FEN>\"}"); },
+    };
+    let id = games.next_id;
+    games.next_id += 1;
+    let json = game_json(id, &board);
+    games.boards.insert(id, board);
+    Response::json(201, json)
+}
+
+/// The board as it was before the last move, replayed from the start.
+fn undo(board: &Board) -> Result<Board> {
+    let history = board.get_history();
+    if history.is_empty() {
+        return Err(anyhow!("There is no move to take back"));
+    }
+    let mut previous = Board::from_fen(board.get_start_fen())?;
+    for m in &history[..history.len() - 1] {
+        previous.play(*m)?;
+        previous.next_turn();
+    }
+    Ok(previous)
+}
+
+fn result(board: &Board) -> &'static str {
+    match outcome(board) {
+        Some(outcome) => outcome.result(),
+        None => "*",
+    }
+}
+
+fn game_json(id: u64, board: &Board) -> String {
+    let spaces = board.get_spaces();
+    let ranks: Vec<String> = spaces.chunks(8)
+        .map(|rank| rank.iter().map(|space| match space {
+            Piece::Piece(piece) => piece_to_char(*piece),
+            Piece::None => '.',
+        }).collect::<String>())
+        .map(|rank| json_string(&rank))
+        .collect();
+    let moves: Vec<String> = board.san_history().iter().map(|san| json_string(san)).collect();
+    let turn = match board.get_turn() {
+        Player::White => "white",
+        Player::Black => "black",
+    };
+
+    format!(
+        "{{\"id\":{},\"fen\":{},\"turn\":\"{}\",\"board\":[{}],\"moves\":[{}],\"result\":{}}}",
+        id,
+        json_string(&board.to_fen()),
+        turn,
+        ranks.join(","),
+        moves.join(","),
+        json_string(result(board)),
+    )
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Reads a string field from a flat JSON object such as `{"move": "e4"}`.
+fn json_field(body: &str, name: &str) -> Option<String> {
+    let mut chars = body.trim().chars().peekable();
+    if chars.next() != Some('{') {
+        return None;
+    }
+    loop {
+        skip_whitespace(&mut chars);
+        if chars.peek() != Some(&'"') {
+            return None;
+        }
+        let key = read_json_string(&mut chars)?;
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(':') {
+            return None;
+        }
+        skip_whitespace(&mut chars);
+        let value = read_json_string(&mut chars)?;
+        if key == name {
+            return Some(value);
+        }
+        skip_whitespace(&mut chars);
+        if chars.next() != Some(',') {
+            return None;
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn read_json_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<String> {
+    if chars.next() != Some('"') {
+        return None;
+    }
+    let mut text = String::new();
+    loop {
+        match chars.next()? {
+            '"' => { return Some(text); },
+            '\\' => match chars.next()? {
+                'n' => text.push('\n'),
+                't' => text.push('\t'),
+                'r' => text.push('\r'),
+                'u' => {
+                    let code: String = (0..4).filter_map(|_| chars.next()).collect();
+                    text.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+                },
+                c => text.push(c),
+            },
+            c => text.push(c),
+        }
+    }
+}
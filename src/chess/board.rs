@@ -1,18 +1,40 @@
 use super::{
     keeper::Keeper,
     utils::{Move, Piece, PieceType, Player, PlayerPiece}, checker::{Snapshot, is_in_check},
+    render::RenderOptions, control::control_map, movement::{castle, castle_move, en_passant_spaces, is_en_passant}, san::to_san,
 };
 use anyhow::{anyhow, Result};
 use colored::*;
+use std::sync::{Mutex, PoisonError};
 
 pub const ROW_LEN: usize = 8;
 pub const BOARD_LEN: usize = 64;
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
 
+#[derive(Debug, Clone)]
 pub struct Board {
     spaces: [Piece; BOARD_LEN],
     turn: Player,
     last_move: Option<Move>,
     keeper: Keeper,
+    history: Vec<Move>,
+    /// The position the history starts from, so the game can be replayed.
+    start_fen: String,
+    san: SanCache,
+    /// Square a pawn skipped over with its double step on the last move.
+    en_passant: Option<usize>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+/// The SAN of the first moves of the history, filled in by `san_history`.
+#[derive(Debug, Default)]
+struct SanCache(Mutex<Vec<String>>);
+
+impl Clone for SanCache {
+    fn clone(&self) -> Self {
+        SanCache(Mutex::new(self.0.lock().unwrap_or_else(PoisonError::into_inner).clone()))
+    }
 }
 
 impl Default for Board {
@@ -69,11 +91,47 @@ impl Board {
             turn: Player::White,
             last_move: None,
             keeper: Keeper::new(&spaces),
+            history: Vec::new(),
+            start_fen: STARTING_FEN.to_string(),
+            san: SanCache::default(),
+            en_passant: None,
+            halfmove_clock: 0,
+            fullmove_number: 1,
             spaces,
         }
     }
 
     pub fn play(&mut self, m: Move) -> Result<()> {
+        let m = self.castle_from_king_move(m);
+        if let Some(options) = m.castle {
+            castle(self, options, self.turn)?;
+
+            let m = castle_move(options, self.turn);
+            self.last_move = Some(m);
+            self.history.push(m);
+            self.keeper.update(&self.spaces);
+            self.en_passant = None;
+            self.halfmove_clock += 1;
+
+            return Ok(());
+        }
+
+        if is_en_passant(self, &m) {
+            let spaces = en_passant_spaces(self, &m);
+            if is_in_check(&Snapshot::from_spaces(spaces), self.turn) {
+                return Err(anyhow!("That move would put you in check!"));
+            }
+
+            self.spaces = spaces;
+            self.last_move = Some(m);
+            self.history.push(m);
+            self.keeper.update(&self.spaces);
+            self.en_passant = None;
+            self.halfmove_clock = 0;
+
+            return Ok(());
+        }
+
         m.is_valid_move(&self.spaces, self.turn)?;
 
         let snap = Snapshot::snap(&self.spaces, &m);
@@ -81,15 +139,25 @@ impl Board {
             return Err(anyhow!("That move would put you in check!"));
         }
 
-        let piece = self.spaces[m.from];
+        let is_pawn = matches!(self.spaces[m.from].extract_value(), Some(PieceType::Pawn));
+        let is_capture = matches!(self.spaces[m.to], Piece::Piece(_));
+
+        let mut piece = self.spaces[m.from];
+        if let (true, Some(promotion)) = (m.is_promotion(&self.spaces), m.promotion) {
+            piece = Piece::Piece(PlayerPiece::new(promotion, self.turn));
+        }
 
         self.spaces[m.from] = Piece::None;
         self.spaces[m.to] = piece;
 
         self.last_move = Some(m);
+        self.history.push(m);
 
         self.keeper.update(&self.spaces);
 
+        self.en_passant = if is_pawn && m.from.abs_diff(m.to) == ROW_LEN * 2 { Some((m.from + m.to) / 2) } else { None };
+        self.halfmove_clock = if is_pawn || is_capture { 0 } else { self.halfmove_clock + 1 };
+
         Ok(())
     }
 
@@ -97,6 +165,23 @@ impl Board {
         self.spaces[piece] = promote_to; 
     }
 
+    /// A king stepping two squares from its starting square is castling, as written in UCI (`e1g1`).
+    fn castle_from_king_move(&self, m: Move) -> Move {
+        let is_king = matches!(self.spaces.get(m.from), Some(Piece::Piece(PlayerPiece { piece: PieceType::King, .. })));
+        let home = match self.turn {
+            Player::White => 60,
+            Player::Black => 4,
+        };
+        if m.castle.is_some() || !is_king || m.from != home {
+            return m;
+        }
+        match m.to {
+            to if to == home + 2 => Move::castle(false),
+            to if to + 2 == home => Move::castle(true),
+            _ => m,
+        }
+    }
+
     pub fn play_no_rules(&mut self, m: Move) -> Result<()> {
         match self.spaces[m.from] {
             Piece::None => {
@@ -135,6 +220,40 @@ impl Board {
 
     pub fn get_keeper(&self) -> &Keeper { &self.keeper }
 
+    pub fn get_history(&self) -> &[Move] { &self.history }
+
+    /// Returns the moves played so far in standard algebraic notation. Moves already written
+    /// out are kept, so only the ones played since the last call are converted.
+    pub fn san_history(&self) -> Vec<String> {
+        let mut san = self.san.0.lock().unwrap_or_else(PoisonError::into_inner);
+        if san.len() < self.history.len() {
+            let mut replay = Board::from_fen(&self.start_fen).unwrap_or_default();
+            for (i, m) in self.history.iter().enumerate() {
+                if i >= san.len() {
+                    san.push(to_san(&replay, m));
+                }
+                if replay.play(*m).is_err() { break; }
+                replay.next_turn();
+            }
+        }
+        san.clone()
+    }
+
+    /// Returns the material White is ahead by, in pawns. Negative if Black is ahead.
+    pub fn material_difference(&self) -> i32 {
+        let white: u32 = self.keeper.get_white_captured().iter().map(|p| p.value()).sum();
+        let black: u32 = self.keeper.get_black_captured().iter().map(|p| p.value()).sum();
+        white as i32 - black as i32
+    }
+
+    pub fn get_en_passant(&self) -> Option<usize> { self.en_passant }
+
+    pub fn get_halfmove_clock(&self) -> u32 { self.halfmove_clock }
+
+    pub fn get_fullmove_number(&self) -> u32 { self.fullmove_number }
+
+    pub fn get_start_fen(&self) -> &str { &self.start_fen }
+
     pub fn next_turn(&mut self) {
         match self.turn {
             Player::White => {
@@ -142,59 +261,175 @@ impl Board {
             }
             Player::Black => {
                 self.turn = Player::White;
+                self.fullmove_number += 1;
             }
         }
     }
 
+    /// Builds a board from its parts. The history starts empty at this position.
+    pub fn setup(spaces: [Piece; BOARD_LEN], turn: Player, keeper: Keeper, en_passant: Option<usize>, halfmove_clock: u32, fullmove_number: u32) -> Self {
+        let mut board = Self {
+            spaces,
+            turn,
+            last_move: None,
+            keeper,
+            history: Vec::new(),
+            start_fen: String::new(),
+            san: SanCache::default(),
+            en_passant,
+            halfmove_clock,
+            fullmove_number,
+        };
+        board.start_fen = board.to_fen();
+        board
+    }
+
     pub fn print(&self) {
-        print!("{} captured pieces: ", "Black".green());
-        for piece in self.keeper.get_black_captured() {
-            print!("{} ", PlayerPiece::new(piece, Player::White).to_colored_string(false));
+        self.print_with(&RenderOptions::default());
+    }
+
+    pub fn print_with(&self, options: &RenderOptions) {
+        let mut shown = *options;
+        if options.control_view.is_some() {
+            for (square, control) in control_map(self).iter().enumerate() {
+                shown.control[square] = (control.white.len() as u8, control.black.len() as u8);
+            }
         }
-        println!("\n");
-        println!(" _____ _____ _____ _____ _____ _____ _____ _____");
+        let options = &shown;
+        let bottom = options.bottom(self.turn);
+        let difference = self.material_difference();
+        let white_line = format!(
+            "{} captured pieces: {}{}",
+            "White".bold(),
+            options.captured_string(&self.keeper.get_white_captured(), Player::Black),
+            if difference > 0 { format!("+{}", difference) } else { String::new() },
+        );
+        let black_line = format!(
+            "{} captured pieces: {}{}",
+            "Black".green(),
+            options.captured_string(&self.keeper.get_black_captured(), Player::White),
+            if difference < 0 { format!("+{}", -difference) } else { String::new() },
+        );
+        let (top_line, bottom_line) = match bottom {
+            Player::White => (black_line, white_line),
+            Player::Black => (white_line, black_line),
+        };
+        let files: Vec<char> = match bottom {
+            Player::White => ('a'..='h').collect(),
+            Player::Black => ('a'..='h').rev().collect(),
+        };
 
-        for row in 0..ROW_LEN {
-            for i in 0..3 {
+        let mut lines = Vec::<String>::new();
+        if options.compact {
+            for row in 0..ROW_LEN {
+                let rank = 8 - (options.index_at(self.turn, row, 0) / ROW_LEN);
+                let mut line = format!("{} ", rank);
                 for col in 0..ROW_LEN {
-                    if i == 0 {
-                        print!("|     ");
-                    }
-                    if i == 1 {
-                        let mut is_last_move = false;
-                        let mut is_moved = false;
-                        if let Some(m) = self.last_move {
-                            if m.from == (row * 8) + col {
-                                is_last_move = true;
-                            }
-                            if m.to == (row * 8) + col {
-                                is_moved = true;
-                            }
+                    let index = options.index_at(self.turn, row, col);
+                    let (is_last_move, is_moved) = self.last_move_markers(index);
+                    line.push_str(&options.square_string(self.spaces[index], index, is_last_move, is_moved, 2));
+                }
+                lines.push(line);
+            }
+            let mut line = "  ".to_string();
+            for file in &files {
+                line.push_str(&format!(" {} ", file));
+            }
+            lines.push(line);
+        } else {
+            lines.push(" _____ _____ _____ _____ _____ _____ _____ _____ ".to_string());
+
+            for row in 0..ROW_LEN {
+                for i in 0..3 {
+                    let mut line = String::new();
+                    for col in 0..ROW_LEN {
+                        let index = options.index_at(self.turn, row, col);
+                        if i == 0 {
+                            line.push_str(&format!("|{}", options.square_ceiling(index)));
                         }
+                        if i == 1 {
+                            let (is_last_move, is_moved) = self.last_move_markers(index);
+                            let square = options.square_string(self.spaces[index], index, is_last_move, is_moved, 4);
 
-                        if col == 0 {
-                            print!( "{}  {}  ", 8 - row, self.spaces[(row * 8) + col] .to_colored_string(is_last_move, is_moved));
-                        } else {
-                            print!( "|  {}  ", self.spaces[(row * 8) + col] .to_colored_string(is_last_move, is_moved));
+                            if col == 0 {
+                                line.push_str(&format!("{}{}", 8 - (index / ROW_LEN), square));
+                            } else {
+                                line.push_str(&format!("|{}", square));
+                            }
+                        }
+                        if i == 2 {
+                            line.push_str(&format!("|{}", options.square_floor(index)));
                         }
                     }
-                    if i == 2 {
-                        print!("|_____");
-                    }
+                    line.push('|');
+                    lines.push(line);
                 }
-                println!("|");
             }
+            let mut line = " ".to_string();
+            for file in &files {
+                line.push_str(&format!("  {}   ", file));
+            }
+            lines.push(line);
         }
-        println!("   a     b     c     d     e     f     g     h\n");
 
-        print!("{} captured pieces: ", "White".bold());
-        for piece in self.keeper.get_white_captured() {
-            print!("{} ", PlayerPiece::new(piece, Player::Black).to_colored_string(false));
+        let panel = if options.move_list { {
+            let start = Board::from_fen(&self.start_fen).unwrap_or_default();
+            move_list_panel(&self.san_history(), start.fullmove_number, start.turn, lines.len())
+        } } else { Vec::new() };
+
+        println!("{}", top_line);
+        if !options.compact {
+            println!();
+        }
+        for (i, line) in lines.iter().enumerate() {
+            match panel.get(i) {
+                Some(entry) => println!("{}    {}", line, entry),
+                None => println!("{}", line),
+            }
+        }
+        if !options.compact {
+            println!();
+        }
+        println!("{}", bottom_line);
+        println!();
+    }
+
+    /// Returns whether the index is the square the last move left, and whether it is the square it landed on.
+    fn last_move_markers(&self, index: usize) -> (bool, bool) {
+        match self.last_move {
+            None => (false, false),
+            Some(m) => (m.from == index, m.to == index),
         }
-        println!("\n");
     }
 }
 
+/// Lays out the moves two per row, numbered from the position the game started in, keeping only
+/// the rows that fit in `height` lines.
+fn move_list_panel(moves: &[String], first_move: u32, turn: Player, height: usize) -> Vec<String> {
+    let mut rows = Vec::<String>::new();
+    let mut number = first_move;
+    let mut rest = moves;
+    if let (Player::Black, Some((black, tail))) = (turn, moves.split_first()) {
+        rows.push(format!("{:>3}. {:<8} {}", number, "...", black));
+        number += 1;
+        rest = tail;
+    }
+    for pair in rest.chunks(2) {
+        let black = pair.get(1).map(|s| s.as_str()).unwrap_or("");
+        rows.push(format!("{:>3}. {:<8} {}", number, pair[0], black));
+        number += 1;
+    }
+
+    let visible = height.saturating_sub(1);
+    if rows.len() > visible {
+        rows.drain(0..rows.len() - visible);
+    }
+
+    let mut panel = vec![format!("{}", "Moves".bold())];
+    panel.append(&mut rows);
+    panel
+}
+
 // _____ _____ _____ _____ _____ _____ _____ _____
 //|     |     |     |     |     |     |     |     |
 //|  R  |  N  |  B  |  K  |  Q  |  B  |  N  |  R  |
@@ -220,3 +455,29 @@ impl Board {
 //|     |     |     |     |     |     |     |     |
 //|  R  |  N  |  B  |  K  |  Q  |  B  |  N  |  R  |
 //|_____|_____|_____|_____|_____|_____|_____|_____|
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::san::parse_san;
+
+    #[test]
+    fn move_list_numbers_from_the_start_position() {
+        let moves: Vec<String> = ["Kd7", "Kf2", "Ke6"].iter().map(|s| s.to_string()).collect();
+        let panel = move_list_panel(&moves, 40, Player::Black, 10);
+        assert_eq!(panel[1], format!(" 40. {:<8} Kd7", "..."));
+        assert_eq!(panel[2], format!(" 41. {:<8} Ke6", "Kf2"));
+    }
+
+    #[test]
+    fn san_history_keeps_up_with_moves_played() {
+        let mut board = Board::new();
+        for san in ["e4", "e5", "Nf3"] {
+            let m = parse_san(san, &board).unwrap();
+            board.play(m).unwrap();
+            board.next_turn();
+            assert_eq!(board.san_history().last().map(String::as_str), Some(san));
+        }
+        assert_eq!(board.clone().san_history(), vec!["e4", "e5", "Nf3"]);
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::{Board, ROW_LEN},
+    interpret::parse_uci,
+    pgn::PgnGame,
+    san::{square_name, to_uci},
+    utils::{Move, Piece, PieceType, Player, PlayerPiece},
+    zobrist::polyglot_key,
+};
+
+/// Bytes in one Polyglot entry: key (8), move (2), weight (2), learn (4), all big-endian.
+const ENTRY_LEN: usize = 16;
+
+/// One line of a Polyglot book.
+#[derive(Clone, Copy)]
+pub struct BookEntry {
+    pub key: u64,
+    /// The move packed as Polyglot does it: to file, to rank, from file, from rank, promotion, 3 bits each.
+    pub raw_move: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+/// How to choose between the book moves of a position.
+#[derive(Clone, Copy)]
+pub enum Selection {
+    /// Always the move with the highest weight.
+    Best,
+    /// At random, in proportion to the weights.
+    Weighted,
+}
+
+/// A Polyglot opening book, with its entries sorted by key.
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open(path: impl AsRef<Path>) -> Result<Book> {
+        let bytes = fs::read(path.as_ref()).with_context(|| format!("Could not read {}", path.as_ref().display()))?;
+        Book::from_bytes(&bytes).with_context(|| format!("{} is not a Polyglot book", path.as_ref().display()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Book> {
+        if !bytes.len().is_multiple_of(ENTRY_LEN) {
+            return Err(anyhow!("The book is {} bytes long, not a whole number of entries!", bytes.len()));
+        }
+        let mut entries: Vec<BookEntry> = bytes.chunks(ENTRY_LEN)
+            .map(|chunk| BookEntry {
+                key: u64::from_be_bytes(chunk[0..8].try_into().unwrap()),
+                raw_move: u16::from_be_bytes(chunk[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(chunk[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(chunk[12..16].try_into().unwrap()),
+            })
+            .collect();
+        // Books are meant to be sorted already; sorting again costs little and makes lookups safe.
+        entries.sort_by_key(|e| e.key);
+        Ok(Book { entries })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.entries.len() * ENTRY_LEN);
+        for entry in &self.entries {
+            bytes.extend_from_slice(&entry.key.to_be_bytes());
+            bytes.extend_from_slice(&entry.raw_move.to_be_bytes());
+            bytes.extend_from_slice(&entry.weight.to_be_bytes());
+            bytes.extend_from_slice(&entry.learn.to_be_bytes());
+        }
+        bytes
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path.as_ref(), self.to_bytes()).with_context(|| format!("Could not write {}", path.as_ref().display()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The book moves for the position with their weights, heaviest first. Moves that aren't legal
+    /// here (a key collision, or a broken book) are left out.
+    pub fn moves(&self, board: &Board) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board);
+        let start = self.entries.partition_point(|e| e.key < key);
+        let mut moves: Vec<(Move, u16)> = self.entries[start..].iter()
+            .take_while(|e| e.key == key)
+            .filter_map(|e| decode_move(board, e.raw_move).map(|m| (m, e.weight)))
+            .collect();
+        moves.sort_by_key(|(_, weight)| std::cmp::Reverse(*weight));
+        moves
+    }
+
+    /// Picks a book move for the position, if there is one. `random` is only used for `Weighted`.
+    pub fn pick(&self, board: &Board, selection: Selection, random: &mut Random) -> Option<Move> {
+        let moves = self.moves(board);
+        match selection {
+            Selection::Best => moves.first().map(|(m, _)| *m),
+            Selection::Weighted => {
+                let total: u64 = moves.iter().map(|(_, w)| *w as u64).sum();
+                if total == 0 {
+                    return moves.first().map(|(m, _)| *m);
+                }
+                let mut roll = random.next_u64() % total;
+                for (m, weight) in &moves {
+                    if roll < *weight as u64 {
+                        return Some(*m);
+                    }
+                    roll -= *weight as u64;
+                }
+                None
+            },
+        }
+    }
+
+    /// Builds a book from the first `plies` moves of each game. A move gets 2 points each time it
+    /// was played by the winner, 1 for a draw or an unfinished game and none for the loser.
+    pub fn from_games(games: &[PgnGame], plies: usize) -> Result<Book> {
+        let mut weights = BTreeMap::<(u64, u16), u64>::new();
+
+        for game in games {
+            let mut board = Board::from_fen(game.board.get_start_fen())?;
+            for m in game.board.get_history().iter().take(plies) {
+                let points = match (game.result(), board.get_turn()) {
+                    ("1-0", Player::White) | ("0-1", Player::Black) => 2,
+                    ("1-0", Player::Black) | ("0-1", Player::White) => 0,
+                    _ => 1,
+                };
+                *weights.entry((polyglot_key(&board), encode_move(&board, m))).or_default() += points;
+                board.play(*m)?;
+                board.next_turn();
+            }
+        }
+
+        // Weights have to fit in 16 bits; scale down each position that doesn't.
+        let mut entries = Vec::<BookEntry>::new();
+        let mut by_key = BTreeMap::<u64, Vec<(u16, u64)>>::new();
+        for ((key, raw_move), weight) in weights {
+            by_key.entry(key).or_default().push((raw_move, weight));
+        }
+        for (key, moves) in by_key {
+            let heaviest = moves.iter().map(|(_, w)| *w).max().unwrap_or(0);
+            let divisor = heaviest.div_ceil(u16::MAX as u64).max(1);
+            for (raw_move, weight) in moves {
+                let weight = (weight / divisor) as u16;
+                if weight > 0 {
+                    entries.push(BookEntry { key, raw_move, weight, learn: 0 });
+                }
+            }
+        }
+
+        Ok(Book { entries })
+    }
+}
+
+/// Turns a Polyglot move into a legal move on the board. Castling is written as the king taking
+/// its own rook (`e1h1`), so those are turned back into the king's two-square step.
+pub fn decode_move(board: &Board, raw: u16) -> Option<Move> {
+    let square = |file: u16, rank: u16| (ROW_LEN - 1 - rank as usize) * ROW_LEN + file as usize;
+    let to = square(raw & 7, (raw >> 3) & 7);
+    let from = square((raw >> 6) & 7, (raw >> 9) & 7);
+
+    let mut text = format!("{}{}", square_name(from), square_name(to));
+    match (raw >> 12) & 7 {
+        0 => { },
+        1 => text.push('n'),
+        2 => text.push('b'),
+        3 => text.push('r'),
+        4 => text.push('q'),
+        _ => { return None; },
+    }
+
+    let is_king = matches!(board.get_space(from), Some(Piece::Piece(PlayerPiece { piece: PieceType::King, .. })));
+    if is_king {
+        text = match text.as_str() {
+            "e1h1" => "e1g1".to_string(),
+            "e1a1" => "e1c1".to_string(),
+            "e8h8" => "e8g8".to_string(),
+            "e8a8" => "e8c8".to_string(),
+            _ => text,
+        };
+    }
+
+    parse_uci(&text, board).ok()
+}
+
+/// Packs a move the way Polyglot books store it, with castling as the king taking its rook.
+pub fn encode_move(board: &Board, m: &Move) -> u16 {
+    let uci = to_uci(m);
+    let is_king = matches!(board.get_space(m.from), Some(Piece::Piece(PlayerPiece { piece: PieceType::King, .. })));
+    let uci = match (is_king, uci.as_str()) {
+        (true, "e1g1") => "e1h1".to_string(),
+        (true, "e1c1") => "e1a1".to_string(),
+        (true, "e8g8") => "e8h8".to_string(),
+        (true, "e8c8") => "e8a8".to_string(),
+        _ => uci,
+    };
+
+    let bytes = uci.as_bytes();
+    let file = |i: usize| (bytes[i] - b'a') as u16;
+    let rank = |i: usize| (bytes[i] - b'1') as u16;
+    let promotion = match m.promotion {
+        None | Some(PieceType::Pawn) | Some(PieceType::King) => 0,
+        Some(PieceType::Knight) => 1,
+        Some(PieceType::Bishop) => 2,
+        Some(PieceType::Rook) => 3,
+        Some(PieceType::Queen) => 4,
+    };
+
+    file(2) | rank(3) << 3 | file(0) << 6 | rank(1) << 9 | promotion << 12
+}
+
+/// Xorshift random numbers; good enough to vary the opening.
+pub struct Random(u64);
+
+impl Random {
+    /// The same seed always gives the same numbers.
+    pub fn seeded(seed: u64) -> Self {
+        // Xorshift never leaves zero, so the seed is mixed with a constant first.
+        Random(seed ^ 0x9E3779B97F4A7C15)
+    }
+
+    pub fn from_clock() -> Self {
+        Random::seeded(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.0 = x;
+        x
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pgn::parse_pgn;
+
+    fn uci(board: &Board, text: &str) -> Move {
+        parse_uci(text, board).unwrap()
+    }
+
+    #[test]
+    fn castling_is_stored_as_the_king_taking_its_rook() {
+        let white = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+        let black = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+        // Files and ranks count from 0: e1h1 is to (7, 0) from (4, 0), e8a8 is to (0, 7) from (4, 7).
+        for (board, king_step, raw) in [
+            (&white, "e1g1", 7 | 4 << 6),
+            (&white, "e1c1", 4 << 6),
+            (&black, "e8g8", 7 | 7 << 3 | 4 << 6 | 7 << 9),
+            (&black, "e8c8", 7 << 3 | 4 << 6 | 7 << 9),
+        ] {
+            let m = uci(board, king_step);
+            assert_eq!(encode_move(board, &m), raw, "{}", king_step);
+            assert_eq!(decode_move(board, raw), Some(m), "{}", king_step);
+        }
+    }
+
+    #[test]
+    fn promotions_use_the_top_bits() {
+        let board = Board::from_fen("8/P3k3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        // a7a8: to a8 is file 0 rank 7, from a7 is file 0 rank 6.
+        let plain = 7 << 3 | 6 << 9;
+        for (text, bits) in [("a7a8n", 1), ("a7a8b", 2), ("a7a8r", 3), ("a7a8q", 4)] {
+            let m = uci(&board, text);
+            let raw = encode_move(&board, &m);
+            assert_eq!(raw, plain | bits << 12, "{}", text);
+            assert_eq!(decode_move(&board, raw), Some(m), "{}", text);
+        }
+        assert_eq!(decode_move(&board, plain | 5 << 12), None);
+    }
+
+    #[test]
+    fn books_built_from_games_survive_a_round_trip() {
+        let games = parse_pgn("[Result \"1-0\"]\n\n1. e4 e5 2. Nf3 1-0\n\n[Result \"1/2-1/2\"]\n\n1. e4 c5 1/2-1/2\n").unwrap();
+        let book = Book::from_games(&games, 2).unwrap();
+        let book = Book::from_bytes(&book.to_bytes()).unwrap();
+        // e5 was only played by the loser, so it gets no entry.
+        assert_eq!(book.len(), 2);
+
+        let mut board = Board::new();
+        // Played by the winner once and in a draw once.
+        assert_eq!(book.moves(&board), vec![(uci(&board, "e2e4"), 3)]);
+
+        board.play(uci(&board, "e2e4")).unwrap();
+        board.next_turn();
+        assert_eq!(book.moves(&board), vec![(uci(&board, "c7c5"), 1)]);
+    }
+
+    #[test]
+    fn lookups_find_every_entry_with_the_key_and_no_others() {
+        let board = Board::new();
+        let key = polyglot_key(&board);
+        let e4 = encode_move(&board, &uci(&board, "e2e4"));
+        let d4 = encode_move(&board, &uci(&board, "d2d4"));
+        let c4 = encode_move(&board, &uci(&board, "c2c4"));
+        let entry = |key, raw_move, weight| BookEntry { key, raw_move, weight, learn: 0 };
+        let book = Book { entries: vec![
+            entry(key - 1, c4, 50),
+            entry(key, d4, 10),
+            entry(key, e4, 20),
+            entry(key + 1, c4, 50),
+        ] };
+
+        assert_eq!(book.moves(&board), vec![(uci(&board, "e2e4"), 20), (uci(&board, "d2d4"), 10)]);
+        assert_eq!(book.pick(&board, Selection::Best, &mut Random::seeded(0)), Some(uci(&board, "e2e4")));
+
+        let picks = |seed| (0..20).map(|_| book.pick(&board, Selection::Weighted, &mut Random::seeded(seed))).collect::<Vec<_>>();
+        assert_eq!(picks(7), picks(7));
+        let mut random = Random::seeded(7);
+        let picked: Vec<_> = (0..50).filter_map(|_| book.pick(&board, Selection::Weighted, &mut random)).collect();
+        assert!(picked.contains(&uci(&board, "e2e4")) && picked.contains(&uci(&board, "d2d4")));
+    }
+}
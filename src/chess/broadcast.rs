@@ -0,0 +1,275 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::{mpsc::{channel, Sender}, Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::Board,
+    game::format_clock,
+    interpret::parse_uci,
+    render::RenderOptions,
+    san::{to_san, to_uci},
+};
+
+// Spectators only listen. Each message is one line.
+//
+// On joining, a spectator gets a snapshot of the game:
+//   PLAYERS <white name>|<black name>
+//   START <fen of the starting position>
+//   MOVES <uci moves so far>
+//   FEN <current fen>
+//   CLOCK <white ms> <black ms>     (when the game has clocks)
+//   RESULT <result> <reason>        (when the game is over)
+//
+// After that, as the game goes on:
+//   MOVE <uci> <san>
+//   FEN <fen>
+//   CLOCK <white ms> <black ms>
+//   RESULT <result> <reason>
+//   RESET, followed by a new snapshot, when the game is replaced or taken back
+
+pub const DEFAULT_SPECTATOR_PORT: u16 = 7879;
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What spectators have been told so far.
+#[derive(Default)]
+struct Shared {
+    /// One writer thread per spectator takes the text from here, so a slow spectator never
+    /// holds up the game or the others.
+    spectators: Vec<Sender<String>>,
+    white_name: String,
+    black_name: String,
+    start_fen: String,
+    moves: Vec<String>,
+    fen: String,
+    clocks: Option<(Duration, Duration)>,
+    result: Option<String>,
+}
+
+impl Shared {
+    fn snapshot(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("PLAYERS {}|{}", self.white_name, self.black_name),
+            format!("START {}", self.start_fen),
+            format!("MOVES {}", self.moves.join(" ")),
+            format!("FEN {}", self.fen),
+        ];
+        if let Some(clocks) = self.clocks {
+            lines.push(clock_line(clocks));
+        }
+        if let Some(result) = &self.result {
+            lines.push(format!("RESULT {}", result));
+        }
+        lines
+    }
+
+    /// Queues the lines for every spectator, dropping the ones whose connection has failed.
+    fn send(&mut self, lines: &[String]) {
+        if lines.is_empty() {
+            return;
+        }
+        let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        self.spectators.retain(|spectator| spectator.send(text.clone()).is_ok());
+    }
+}
+
+/// Writes everything queued for one spectator until the connection fails or the broadcast ends.
+fn spawn_writer(mut stream: TcpStream) -> Sender<String> {
+    let (sender, queue) = channel::<String>();
+    thread::spawn(move || {
+        for text in queue {
+            if stream.write_all(text.as_bytes()).is_err() { break; }
+        }
+    });
+    sender
+}
+
+fn clock_line((white, black): (Duration, Duration)) -> String {
+    format!("CLOCK {} {}", white.as_millis(), black.as_millis())
+}
+
+/// Streams a running game to any number of read-only spectators over TCP.
+#[derive(Clone)]
+pub struct Broadcaster {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl Broadcaster {
+    /// Starts accepting spectators on the port. The game is set with `update`.
+    pub fn start(port: u16) -> Result<Self> {
+        let listener = TcpListener::bind(("0.0.0.0", port)).with_context(|| format!("Could not listen on port {}", port))?;
+        let shared = Arc::new(Mutex::new(Shared {
+            start_fen: Board::new().get_start_fen().to_string(),
+            fen: Board::new().to_fen(),
+            ..Shared::default()
+        }));
+
+        let accept_shared = Arc::clone(&shared);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+                let spectator = spawn_writer(stream);
+                let mut shared = accept_shared.lock().unwrap_or_else(PoisonError::into_inner);
+                let text: String = shared.snapshot().iter().map(|line| format!("{}\n", line)).collect();
+                if spectator.send(text).is_ok() {
+                    shared.spectators.push(spectator);
+                }
+            }
+        });
+
+        Ok(Self { shared })
+    }
+
+    pub fn set_players(&self, white: &str, black: &str) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.white_name != white || shared.black_name != black {
+            shared.white_name = white.to_string();
+            shared.black_name = black.to_string();
+            let line = format!("PLAYERS {}|{}", white, black);
+            shared.send(&[line]);
+        }
+    }
+
+    /// Tells spectators what changed since the last update: new moves and clocks, or the whole game
+    /// when the history no longer follows on from what they have seen.
+    pub fn update(&self, board: &Board, clocks: Option<(Duration, Duration)>) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        let moves: Vec<String> = board.get_history().iter().map(to_uci).collect();
+
+        let follows_on = shared.start_fen == board.get_start_fen() && moves.starts_with(&shared.moves);
+        if !follows_on {
+            shared.start_fen = board.get_start_fen().to_string();
+            shared.moves = moves;
+            shared.fen = board.to_fen();
+            shared.clocks = clocks;
+            shared.result = None;
+            let mut lines = vec!["RESET".to_string()];
+            lines.extend(shared.snapshot());
+            shared.send(&lines);
+            return;
+        }
+
+        let mut lines = Vec::<String>::new();
+        if moves.len() > shared.moves.len() {
+            let mut replay = Board::from_fen(board.get_start_fen()).unwrap_or_default();
+            for (i, m) in board.get_history().iter().enumerate() {
+                if i >= shared.moves.len() {
+                    lines.push(format!("MOVE {} {}", to_uci(m), to_san(&replay, m)));
+                }
+                if replay.play(*m).is_err() { break; }
+                replay.next_turn();
+            }
+            shared.moves = moves;
+            shared.fen = board.to_fen();
+            lines.push(format!("FEN {}", shared.fen));
+        }
+        if let Some(clocks) = clocks {
+            let seconds = |(white, black): (Duration, Duration)| (white.as_secs(), black.as_secs());
+            if shared.clocks.map(seconds) != Some(seconds(clocks)) {
+                shared.clocks = Some(clocks);
+                lines.push(clock_line(clocks));
+            }
+        }
+        shared.send(&lines);
+    }
+
+    /// Announces the end of the game, once.
+    pub fn finish(&self, result: &str) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if shared.result.is_none() {
+            shared.result = Some(result.to_string());
+            shared.send(&[format!("RESULT {}", result)]);
+        }
+    }
+}
+
+/// Connects to a broadcast and redraws the board as the game goes on.
+pub fn watch(address: &str) -> Result<()> {
+    let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_SPECTATOR_PORT) };
+    let stream = TcpStream::connect(&address).with_context(|| format!("Could not connect to {}", address))?;
+
+    let mut board = Board::new();
+    let mut players = ("White".to_string(), "Black".to_string());
+    let mut clocks: Option<(u64, u64)> = None;
+    let mut result: Option<String> = None;
+    let mut last = String::new();
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        let (command, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        match command {
+            "PLAYERS" => {
+                let (white, black) = rest.split_once('|').unwrap_or((rest, ""));
+                players = (white.to_string(), black.to_string());
+            },
+            "START" => { board = Board::from_fen(rest)?; },
+            "MOVES" => {
+                for text in rest.split_whitespace() {
+                    let m = parse_uci(text, &board)?;
+                    board.play(m)?;
+                    board.next_turn();
+                }
+            },
+            "MOVE" => {
+                let (uci, san) = rest.split_once(' ').unwrap_or((rest, rest));
+                let m = parse_uci(uci, &board)?;
+                board.play(m)?;
+                board.next_turn();
+                last = san.to_string();
+            },
+            "FEN" => {
+                if board.to_fen() != rest {
+                    return Err(anyhow!("Lost track of the game: expected {} but have {}", rest, board.to_fen()));
+                }
+            },
+            "CLOCK" => {
+                let times: Vec<u64> = rest.split_whitespace().filter_map(|t| t.parse().ok()).collect();
+                if times.len() == 2 {
+                    clocks = Some((times[0], times[1]));
+                }
+            },
+            "RESULT" => { result = Some(rest.to_string()); },
+            "RESET" => {
+                result = None;
+                last.clear();
+            },
+            _ => { continue; },
+        }
+
+        // A snapshot arrives as several lines in a row; draw once the stream goes quiet.
+        if lines_pending(command) {
+            continue;
+        }
+        clearscr!();
+        board.print_with(&RenderOptions::default());
+        match clocks {
+            Some((white, black)) => println!(
+                "{} {}    {} {}",
+                players.0,
+                format_clock(Duration::from_millis(white)),
+                players.1,
+                format_clock(Duration::from_millis(black)),
+            ),
+            None => println!("{} vs {}", players.0, players.1),
+        }
+        if !last.is_empty() {
+            println!("Last move: {}", last);
+        }
+        if let Some(result) = &result {
+            println!("Game over: {}", result);
+        }
+        println!("Watching {} (Ctrl+C to stop)", address);
+    }
+
+    println!("The broadcast has ended.");
+    Ok(())
+}
+
+/// Whether more lines always follow this one before the picture is complete.
+fn lines_pending(command: &str) -> bool {
+    matches!(command, "RESET" | "START" | "MOVES" | "MOVE")
+}
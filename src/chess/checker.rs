@@ -1,5 +1,5 @@
 use std::mem;
-use super::{utils::{Piece, Player, Move, PieceType}, board::{BOARD_LEN, Board}, keeper::Keeper};
+use super::{utils::{Piece, Player, PlayerPiece, Move, PieceType}, board::{BOARD_LEN, Board}, keeper::Keeper, movement::legal_moves};
 
 pub struct Snapshot {
     spaces: [Piece; BOARD_LEN],
@@ -10,6 +10,10 @@ impl Snapshot {
         Snapshot { spaces: board.get_spaces() }
     }
 
+    pub fn from_spaces(spaces: [Piece; BOARD_LEN]) -> Self {
+        Snapshot { spaces }
+    }
+
     pub fn snap(board: &[Piece; BOARD_LEN], m: &Move) -> Self {
         let mut spaces = *board;
         spaces[m.to] = spaces[m.from];
@@ -67,3 +71,55 @@ pub fn pawns_to_promote(snap: &Snapshot) -> Vec<usize> {
     pawns
 }
 
+
+/// Returns the indices of the player's pieces that attack the target, whether or not it is occupied.
+pub fn attackers(snap: &Snapshot, target: usize, player: Player) -> Vec<usize> {
+    let mut spaces = snap.spaces;
+    let opponent = player.opponent();
+    // Put an enemy piece on the target so pawns count their diagonal captures, not their pushes.
+    spaces[target] = Piece::Piece(PlayerPiece::new(PieceType::Pawn, opponent));
+
+    let mut found = Vec::<usize>::new();
+    for (from, piece) in snap.spaces.iter().enumerate() {
+        match piece.extract_player() {
+            Some(p) if mem::discriminant(&p) == mem::discriminant(&player) => { },
+            _ => { continue; },
+        }
+        if Move::new(from, target).is_valid_move(&spaces, player).is_ok() {
+            found.push(from);
+        }
+    }
+
+    found
+}
+
+pub enum Outcome {
+    Checkmate(Player),
+    Stalemate,
+}
+
+/// Returns how the game ended, if the side to move has no legal moves. Checkmate holds the winner.
+pub fn outcome(board: &Board) -> Option<Outcome> {
+    if !legal_moves(board).is_empty() {
+        return None;
+    }
+
+    let turn = board.get_turn();
+    if is_in_check(&Snapshot::new(board), turn) {
+        let winner = turn.opponent();
+        return Some(Outcome::Checkmate(winner));
+    }
+
+    Some(Outcome::Stalemate)
+}
+
+impl Outcome {
+    /// The result as written in PGN.
+    pub fn result(&self) -> &'static str {
+        match self {
+            Outcome::Checkmate(Player::White) => "1-0",
+            Outcome::Checkmate(Player::Black) => "0-1",
+            Outcome::Stalemate => "1/2-1/2",
+        }
+    }
+}
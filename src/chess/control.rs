@@ -0,0 +1,93 @@
+use super::{
+    board::{Board, BOARD_LEN},
+    checker::{attackers, Snapshot},
+    movement::{index_in_raycast, raycast, Direction},
+    utils::{Piece, PieceType, Player},
+};
+
+// Control of a square counts every piece that attacks it, plus the sliders lined up behind those
+// attackers on the same ray: a rook doubled behind a rook, or a queen behind a bishop or a pawn,
+// attacks the square "through" the piece in front once that piece has captured.
+
+/// A piece attacking a square.
+#[derive(Clone, Copy)]
+pub struct Attacker {
+    pub square: usize,
+    /// Attacks through another attacker rather than directly.
+    pub xray: bool,
+}
+
+/// The attackers of one square for each side.
+#[derive(Default)]
+pub struct SquareControl {
+    pub white: Vec<Attacker>,
+    pub black: Vec<Attacker>,
+}
+
+impl SquareControl {
+    pub fn of(&self, player: Player) -> &[Attacker] {
+        match player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        }
+    }
+
+    /// White's attackers minus Black's: positive when White controls the square.
+    pub fn balance(&self) -> i32 {
+        self.white.len() as i32 - self.black.len() as i32
+    }
+}
+
+/// The attackers of `target`, x-rays included.
+pub fn square_control(board: &Board, target: usize) -> SquareControl {
+    let spaces = board.get_spaces();
+    let snap = Snapshot::new(board);
+    let mut control = SquareControl::default();
+
+    for player in [Player::White, Player::Black] {
+        for from in attackers(&snap, target, player) {
+            push(&mut control, player, Attacker { square: from, xray: false });
+            let mut blocker = from;
+            while let Some(next) = behind(&spaces, target, blocker) {
+                if let Some(owner) = spaces[next].extract_player() {
+                    push(&mut control, owner, Attacker { square: next, xray: true });
+                }
+                blocker = next;
+            }
+        }
+    }
+
+    control
+}
+
+/// The control of every square, indexed like the board.
+pub fn control_map(board: &Board) -> Vec<SquareControl> {
+    (0..BOARD_LEN).map(|target| square_control(board, target)).collect()
+}
+
+/// The slider behind `blocker`, on the ray from `target` through it, that would attack `target`
+/// if `blocker` were gone.
+pub(crate) fn behind(spaces: &[Piece; BOARD_LEN], target: usize, blocker: usize) -> Option<usize> {
+    let direction = direction_to(target, blocker)?;
+    let next = raycast(spaces, blocker, direction)?;
+    let orthogonal = Direction::ORTHOGONAL.iter().any(|d| std::mem::discriminant(d) == std::mem::discriminant(&direction));
+    match spaces[next].extract_value()? {
+        PieceType::Queen => Some(next),
+        PieceType::Rook if orthogonal => Some(next),
+        PieceType::Bishop if !orthogonal => Some(next),
+        _ => None,
+    }
+}
+
+/// The direction from one square to another, if they share a line.
+fn direction_to(from: usize, to: usize) -> Option<Direction> {
+    Direction::ORTHOGONAL.into_iter().chain(Direction::DIAGONAL).find(|&d| index_in_raycast(from, d, to))
+}
+
+fn push(control: &mut SquareControl, player: Player, attacker: Attacker) {
+    if control.of(player).iter().any(|a| a.square == attacker.square) { return; }
+    match player {
+        Player::White => control.white.push(attacker),
+        Player::Black => control.black.push(attacker),
+    }
+}
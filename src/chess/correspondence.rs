@@ -0,0 +1,260 @@
+use std::{
+    fs,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::Board,
+    checker::outcome,
+    interpret::parse_uci,
+    pgn::{parse_pgn, PgnGame},
+    san::{parse_san, to_san, to_uci},
+    utils::Player,
+};
+
+// A correspondence game is a PGN file with a few extra tags:
+//
+//   [Mode "Correspondence"]
+//   [ToMove "White"]                 the side whose move it is
+//   [Checksum "<16 hex digits>"]     over the players, start position, moves, times and result
+//
+// and each move followed by the time it was entered, as `{[%ts 2026-10-19T09:30:00Z]}`.
+
+const MODE: &str = "Correspondence";
+const TIMESTAMP_PREFIX: &str = "[%ts ";
+/// How far a move's time may be ahead of this computer's clock.
+const CLOCK_SKEW: u64 = 5 * 60;
+
+/// Starts a correspondence game and writes it to `path`, which must not exist yet.
+pub fn create(path: impl AsRef<Path>, board: Board, white: &str, black: &str) -> Result<PgnGame> {
+    let path = path.as_ref();
+    if path.exists() {
+        return Err(anyhow!("{} already exists!", path.display()));
+    }
+
+    let mut game = PgnGame::new(board);
+    let now = now();
+    game.set_tag("Event", "Correspondence game");
+    game.set_tag("Date", &format_timestamp(now)[..10].replace('-', "."));
+    game.set_tag("White", white);
+    game.set_tag("Black", black);
+    game.set_tag("Mode", MODE);
+    write(path, &mut game, &[])?;
+    Ok(game)
+}
+
+/// Reads a correspondence game, refusing it if the history doesn't check out.
+pub fn load(path: impl AsRef<Path>) -> Result<PgnGame> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    let mut games = parse_pgn(&text).with_context(|| format!("{} does not replay", path.display()))?;
+    if games.len() != 1 {
+        return Err(anyhow!("{} should hold exactly one game!", path.display()));
+    }
+    let game = games.remove(0);
+    verify(&game)?;
+    Ok(game)
+}
+
+/// Plays one move for `player` and rewrites the file. Returns the game after the move.
+pub fn submit(path: impl AsRef<Path>, player: Player, text: &str) -> Result<PgnGame> {
+    let path = path.as_ref();
+    let mut game = load(path)?;
+
+    if game.result() != "*" {
+        return Err(anyhow!("The game is over: {}", game.result()));
+    }
+    let turn = game.board.get_turn();
+    if std::mem::discriminant(&turn) != std::mem::discriminant(&player) {
+        return Err(anyhow!("It is {}'s move, not yours!", side_name(turn)));
+    }
+
+    let mut times = timestamps(&game)?;
+    let now = now();
+    if let Some(last) = times.last() {
+        if now < *last {
+            return Err(anyhow!("The last move is dated after this computer's clock; check the time."));
+        }
+    }
+
+    let text = text.trim();
+    let m = parse_uci(text, &game.board).or_else(|_| parse_san(text, &game.board))?;
+    game.board.play(m)?;
+    game.board.next_turn();
+    times.push(now);
+
+    if let Some(outcome) = outcome(&game.board) {
+        game.set_tag("Result", outcome.result());
+    }
+    write(path, &mut game, &times)?;
+    Ok(game)
+}
+
+/// Works out which side a name given on the command line plays: `white`, `black` or a player's name.
+pub fn player_for(game: &PgnGame, who: &str) -> Result<Player> {
+    match who.to_lowercase().as_str() {
+        "white" | "w" => Ok(Player::White),
+        "black" | "b" => Ok(Player::Black),
+        _ if game.tag("White") == Some(who) => Ok(Player::White),
+        _ if game.tag("Black") == Some(who) => Ok(Player::Black),
+        _ => Err(anyhow!("'{}' is not a player in this game!", who)),
+    }
+}
+
+/// The time each move was entered, in seconds since 1970.
+pub fn timestamps(game: &PgnGame) -> Result<Vec<u64>> {
+    (0..game.board.get_history().len())
+        .map(|ply| {
+            game.comments.get(&ply)
+                .and_then(|comment| comment.split(TIMESTAMP_PREFIX).nth(1))
+                .and_then(|rest| rest.split(']').next())
+                .and_then(parse_timestamp)
+                .ok_or_else(|| anyhow!("Move {} has no time on it; the file has been edited.", ply + 1))
+        })
+        .collect()
+}
+
+fn verify(game: &PgnGame) -> Result<()> {
+    if game.tag("Mode") != Some(MODE) {
+        return Err(anyhow!("This is not a correspondence game!"));
+    }
+
+    let times = timestamps(game)?;
+    if times.windows(2).any(|pair| pair[1] < pair[0]) {
+        return Err(anyhow!("The move times go backwards; the file has been edited."));
+    }
+    if times.last().is_some_and(|last| *last > now() + CLOCK_SKEW) {
+        return Err(anyhow!("The last move is dated in the future; the file has been edited."));
+    }
+
+    let to_move = side_name(game.board.get_turn());
+    if game.tag("ToMove").map(|t| t.to_lowercase()) != Some(to_move.to_string()) {
+        return Err(anyhow!("The file says the wrong side is to move; it has been edited."));
+    }
+
+    if game.tag("Checksum") != Some(checksum(game, &times).as_str()) {
+        return Err(anyhow!("The checksum doesn't match the moves; the file has been edited."));
+    }
+
+    Ok(())
+}
+
+fn write(path: &Path, game: &mut PgnGame, times: &[u64]) -> Result<()> {
+    game.comments.clear();
+    for (ply, time) in times.iter().enumerate() {
+        game.comments.insert(ply, format!("{}{}]", TIMESTAMP_PREFIX, format_timestamp(*time)));
+    }
+    let to_move = match game.board.get_turn() {
+        Player::White => "White",
+        Player::Black => "Black",
+    };
+    game.set_tag("ToMove", to_move);
+    // The opening is named again from the moves each time.
+    game.tags.retain(|(key, _)| key != "ECO" && key != "Opening");
+    let sum = checksum(game, times);
+    game.set_tag("Checksum", &sum);
+
+    // Write next to the file and rename, so a crash can't leave half a game behind.
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, game.to_pgn()).with_context(|| format!("Could not write {}", temporary.display()))?;
+    fs::rename(&temporary, path).with_context(|| format!("Could not replace {}", path.display()))?;
+    Ok(())
+}
+
+/// A 64-bit FNV-1a hash of everything that makes up the game. It catches edits by hand,
+/// not someone who recomputes it.
+fn checksum(game: &PgnGame, times: &[u64]) -> String {
+    let mut text = format!(
+        "{}\n{}\n{}\n",
+        game.tag("White").unwrap_or(""),
+        game.tag("Black").unwrap_or(""),
+        game.board.get_start_fen(),
+    );
+    for (m, time) in game.board.get_history().iter().zip(times) {
+        text.push_str(&format!("{} {}\n", to_uci(m), time));
+    }
+    text.push_str(game.result());
+
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in text.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)
+}
+
+/// A line for each move with the time it was played.
+pub fn history(game: &PgnGame) -> Result<Vec<String>> {
+    let times = timestamps(game)?;
+    let mut replay = Board::from_fen(game.board.get_start_fen())?;
+    let mut lines = Vec::<String>::new();
+    for (m, time) in game.board.get_history().iter().zip(times) {
+        let number = match replay.get_turn() {
+            Player::White => format!("{}.", replay.get_fullmove_number()),
+            Player::Black => format!("{}...", replay.get_fullmove_number()),
+        };
+        lines.push(format!("{:>5} {:<8} {}", number, to_san(&replay, m), format_timestamp(time)));
+        replay.play(*m)?;
+        replay.next_turn();
+    }
+    Ok(lines)
+}
+
+fn side_name(player: Player) -> &'static str {
+    match player {
+        Player::White => "white",
+        Player::Black => "black",
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Formats seconds since 1970 as a UTC time, `2026-10-19T09:30:00Z`.
+pub fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86400) as i64;
+    let time = seconds % 86400;
+
+    // Days to a civil date, after Howard Hinnant's `civil_from_days`.
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Reads a time written by `format_timestamp`.
+pub fn parse_timestamp(text: &str) -> Option<u64> {
+    let text = text.trim().strip_suffix('Z')?;
+    let (date, time) = text.split_once('T')?;
+    let date: Vec<i64> = date.split('-').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let time: Vec<u64> = time.split(':').map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    if date.len() != 3 || time.len() != 3 || !(1..=12).contains(&date[1]) || !(1..=31).contains(&date[2]) {
+        return None;
+    }
+    if time[0] > 23 || time[1] > 59 || time[2] > 60 {
+        return None;
+    }
+
+    // A civil date to days, after Howard Hinnant's `days_from_civil`.
+    let (month, day) = (date[1], date[2]);
+    let year = if month <= 2 { date[0] - 1 } else { date[0] };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+    if days < 0 {
+        return None;
+    }
+
+    Some(days as u64 * 86400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
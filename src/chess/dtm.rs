@@ -0,0 +1,618 @@
+use std::{fs, path::Path};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::{Board, BOARD_LEN, ROW_LEN},
+    checker::{is_in_check, Snapshot},
+    keeper::Keeper,
+    movement::legal_moves,
+    utils::{Castle, Move, Piece, PieceType, Player, PlayerPiece},
+};
+
+// Distance-to-mate tables for endings of a king and up to two pieces against a bare king, worked
+// out backwards from the mates with the crate's own move generation.
+//
+// A table file is `DTM1`, the ending's name as one length byte and ASCII (`KRK`), the number of
+// positions as a little-endian u32, then one byte per position:
+//
+//   0            draw
+//   1..=127      the side to move mates in that many plies
+//   128..=254    the side to move is mated in (byte - 128) plies; 128 is checkmate on the board
+//   255          not a legal position
+//
+// The stronger side is always White in a table; a position where Black has the pieces is looked
+// up with the board turned around. Positions are indexed by side to move, the two kings and the
+// other pieces, with the white king moved by symmetry into a1-d1-d4 (or onto files a-d when there
+// is a pawn), which cuts the tables to an eighth (a half) of their size.
+
+const MAGIC: &[u8; 4] = b"DTM1";
+const DRAW: u8 = 0;
+const LOSS: u8 = 128;
+const ILLEGAL: u8 = 255;
+/// Not worked out yet; only while generating.
+const UNKNOWN: u8 = 254;
+/// Marks a move that leaves the table; the low byte is the value of the position it leads to.
+const EXTERNAL: u32 = 1 << 31;
+
+/// An ending the generator can build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    Kqk,
+    Krk,
+    Kpk,
+    Kbnk,
+}
+
+pub const ENDINGS: [Ending; 4] = [Ending::Kqk, Ending::Krk, Ending::Kpk, Ending::Kbnk];
+
+impl Ending {
+    pub fn name(self) -> &'static str {
+        match self {
+            Ending::Kqk => "KQK",
+            Ending::Krk => "KRK",
+            Ending::Kpk => "KPK",
+            Ending::Kbnk => "KBNK",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Ending> {
+        ENDINGS.into_iter()
+            .find(|ending| ending.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| anyhow!("'{}' is not one of the endings: KQK, KRK, KPK, KBNK.", name))
+    }
+
+    /// The stronger side's pieces besides the king.
+    fn pieces(self) -> &'static [PieceType] {
+        match self {
+            Ending::Kqk => &[PieceType::Queen],
+            Ending::Krk => &[PieceType::Rook],
+            Ending::Kpk => &[PieceType::Pawn],
+            Ending::Kbnk => &[PieceType::Bishop, PieceType::Knight],
+        }
+    }
+
+    fn has_pawn(self) -> bool {
+        matches!(self, Ending::Kpk)
+    }
+
+    /// Squares the white king is kept to.
+    fn king_slots(self) -> usize {
+        if self.has_pawn() { 32 } else { 10 }
+    }
+
+    fn size(self) -> usize {
+        2 * self.king_slots() * BOARD_LEN.pow(1 + self.pieces().len() as u32)
+    }
+
+    /// The tables this one's promotions lead into.
+    pub fn needs(self) -> &'static [Ending] {
+        match self {
+            Ending::Kpk => &[Ending::Kqk, Ending::Krk],
+            _ => &[],
+        }
+    }
+}
+
+/// Result of a position for the side to move, counted in plies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    fn from_byte(value: u8) -> Option<Dtm> {
+        match value {
+            DRAW => Some(Dtm::Draw),
+            1..=127 => Some(Dtm::Win(value as u32)),
+            ILLEGAL | UNKNOWN => None,
+            _ => Some(Dtm::Loss((value - LOSS) as u32)),
+        }
+    }
+
+    /// In moves, the way mates are usually counted: `Win(31)` is mate in 16.
+    pub fn moves(self) -> u32 {
+        match self {
+            Dtm::Win(plies) => plies.div_ceil(2),
+            Dtm::Loss(plies) => plies / 2,
+            Dtm::Draw => 0,
+        }
+    }
+}
+
+/// A generated table.
+pub struct DtmTable {
+    ending: Ending,
+    values: Vec<u8>,
+}
+
+impl DtmTable {
+    /// Works the table out. `built` must hold the tables named by `ending.needs()`.
+    pub fn generate(ending: Ending, built: &[DtmTable]) -> Result<DtmTable> {
+        for need in ending.needs() {
+            if !built.iter().any(|table| table.ending == *need) {
+                return Err(anyhow!("{} needs the {} table first.", ending.name(), need.name()));
+            }
+        }
+
+        let size = ending.size();
+        let mut values = vec![ILLEGAL; size];
+        let mut offsets = vec![0u32; size + 1];
+        let mut edges = Vec::<u32>::new();
+
+        // Every legal position, its moves and where they lead.
+        for index in 0..size {
+            offsets[index] = edges.len() as u32;
+            let Some(position) = Position::decode(ending, index) else { continue; };
+            let board = position.board();
+            if is_in_check(&Snapshot::new(&board), position.turn.opponent()) { continue; }
+
+            let moves = legal_moves(&board);
+            if moves.is_empty() {
+                values[index] = if is_in_check(&Snapshot::new(&board), position.turn) { LOSS } else { DRAW };
+                continue;
+            }
+            values[index] = UNKNOWN;
+            for m in moves {
+                edges.push(match position.after(&m, built) {
+                    After::Inside(child) => child.encode() as u32,
+                    After::Outside(value) => EXTERNAL | value as u32,
+                });
+            }
+        }
+        offsets[size] = edges.len() as u32;
+
+        let deepest_outside = edges.iter()
+            .filter(|edge| *edge & EXTERNAL != 0)
+            .filter_map(|edge| Dtm::from_byte(*edge as u8))
+            .map(|dtm| match dtm { Dtm::Win(plies) | Dtm::Loss(plies) => plies, Dtm::Draw => 0 })
+            .max()
+            .unwrap_or(0);
+
+        // A position is won in n plies if a move reaches one lost in n - 1, and lost in n plies if
+        // every move reaches a won one and the longest of those wins takes n - 1.
+        let value_of = |values: &[u8], edge: u32| if edge & EXTERNAL != 0 { edge as u8 } else { values[edge as usize] };
+        let mut idle = 0;
+        for plies in 1..LOSS as u32 - 1 {
+            let mut changed = false;
+            for index in 0..size {
+                if values[index] != UNKNOWN { continue; }
+                let children = &edges[offsets[index] as usize..offsets[index + 1] as usize];
+                let found = if plies % 2 == 1 {
+                    children.iter().any(|edge| value_of(&values, *edge) == LOSS + plies as u8 - 1)
+                } else {
+                    let mut longest = 0;
+                    let all_won = children.iter().all(|edge| {
+                        let value = value_of(&values, *edge);
+                        longest = longest.max(value);
+                        (1..LOSS).contains(&value)
+                    });
+                    all_won && longest as u32 == plies - 1
+                };
+                if found {
+                    values[index] = if plies % 2 == 1 { plies as u8 } else { LOSS + plies as u8 };
+                    changed = true;
+                }
+            }
+
+            idle = if changed { 0 } else { idle + 1 };
+            if idle >= 2 && plies > deepest_outside + 1 { break; }
+        }
+
+        // Whatever is left can't be forced either way.
+        for value in values.iter_mut().filter(|value| **value == UNKNOWN) {
+            *value = DRAW;
+        }
+        Ok(DtmTable { ending, values })
+    }
+
+    pub fn ending(&self) -> Ending {
+        self.ending
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let name = self.ending.name();
+        let mut bytes = Vec::with_capacity(MAGIC.len() + 1 + name.len() + 4 + self.values.len());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(self.values.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.values);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<DtmTable> {
+        if bytes.len() < 5 || &bytes[0..4] != MAGIC {
+            return Err(anyhow!("Not a distance-to-mate table!"));
+        }
+        let name_len = bytes[4] as usize;
+        let name = bytes.get(5..5 + name_len).and_then(|name| std::str::from_utf8(name).ok()).ok_or_else(|| anyhow!("The table's name is cut off!"))?;
+        let ending = Ending::from_name(name)?;
+        let count = bytes.get(5 + name_len..9 + name_len)
+            .map(|count| u32::from_le_bytes(count.try_into().unwrap()) as usize)
+            .ok_or_else(|| anyhow!("The table's size is cut off!"))?;
+        let values = &bytes[9 + name_len..];
+        if count != ending.size() || values.len() != count {
+            return Err(anyhow!("The {} table should hold {} positions, not {}!", name, ending.size(), values.len()));
+        }
+        Ok(DtmTable { ending, values: values.to_vec() })
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<DtmTable> {
+        let bytes = fs::read(path.as_ref()).with_context(|| format!("Could not read {}", path.as_ref().display()))?;
+        DtmTable::from_bytes(&bytes).with_context(|| format!("Could not load {}", path.as_ref().display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path.as_ref(), self.to_bytes()).with_context(|| format!("Could not write {}", path.as_ref().display()))
+    }
+
+    /// The result of a position of this ending, or `None` if it's a different ending.
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        let position = Position::from_board(self.ending, board)?;
+        Dtm::from_byte(self.values[position.encode()])
+    }
+
+    fn value(&self, position: &Position) -> u8 {
+        self.values[position.encode()]
+    }
+
+    /// The longest win in the table and a position (as a FEN) where it happens.
+    pub fn longest_win(&self) -> Option<(Dtm, String)> {
+        let (index, value) = self.values.iter().enumerate()
+            .filter(|(_, value)| (1..LOSS).contains(*value))
+            .max_by_key(|(_, value)| **value)?;
+        let position = Position::decode(self.ending, index)?;
+        Some((Dtm::Win(*value as u32), position.board().to_fen()))
+    }
+
+    /// How many legal positions are won, drawn and lost for the side to move.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.values.iter().fold((0, 0, 0), |(won, drawn, lost), value| match *value {
+            ILLEGAL => (won, drawn, lost),
+            DRAW => (won, drawn + 1, lost),
+            1..=127 => (won + 1, drawn, lost),
+            _ => (won, drawn, lost + 1),
+        })
+    }
+}
+
+/// Several tables, looked up by the material on the board.
+#[derive(Default)]
+pub struct DtmTables {
+    tables: Vec<DtmTable>,
+}
+
+impl DtmTables {
+    /// Loads every `.dtm` file in the directory.
+    pub fn open(dir: impl AsRef<Path>) -> Result<DtmTables> {
+        let dir = dir.as_ref();
+        let mut tables = DtmTables::default();
+        for entry in fs::read_dir(dir).with_context(|| format!("Could not read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "dtm") {
+                tables.tables.push(DtmTable::open(&path)?);
+            }
+        }
+        if tables.tables.is_empty() {
+            return Err(anyhow!("No .dtm tables in {}", dir.display()));
+        }
+        Ok(tables)
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        self.tables.iter().find_map(|table| table.probe(board))
+    }
+
+    /// The move that mates soonest when winning, holds the draw when drawn, and lasts longest
+    /// when losing.
+    pub fn best_move(&self, board: &Board) -> Option<(Move, Dtm)> {
+        let dtm = self.probe(board)?;
+        let mut best: Option<(Move, i64)> = None;
+        for m in legal_moves(board) {
+            let mut child = board.clone();
+            if child.play(m).is_err() { continue; }
+            child.next_turn();
+            let score = match self.probe(&child).or_else(|| drawn_by_material(&child)) {
+                // Ranked for the mover: quick wins first, then draws, then slow losses.
+                Some(Dtm::Loss(plies)) => 1000 - plies as i64,
+                Some(Dtm::Draw) => 0,
+                Some(Dtm::Win(plies)) => -1000 + plies as i64,
+                None => { continue; },
+            };
+            if best.is_none_or(|(_, best_score)| score > best_score) {
+                best = Some((m, score));
+            }
+        }
+        best.map(|(m, _)| (m, dtm))
+    }
+}
+
+/// Kings alone, or with a single minor piece, can't mate.
+fn drawn_by_material(board: &Board) -> Option<Dtm> {
+    let keeper = board.get_keeper();
+    let others: Vec<PieceType> = keeper.get_white_pieces().into_iter()
+        .chain(keeper.get_black_pieces())
+        .map(|(piece, _)| piece)
+        .filter(|piece| !matches!(piece, PieceType::King))
+        .collect();
+    match others.as_slice() {
+        [] | [PieceType::Bishop | PieceType::Knight] => Some(Dtm::Draw),
+        _ => None,
+    }
+}
+
+/// Where a move leads: another position of the table, or out of it with a known value.
+enum After {
+    Inside(Position),
+    Outside(u8),
+}
+
+/// A position of a table in its canonical orientation, as (file, row) squares with row 0 the
+/// eighth rank like the board's indices.
+#[derive(Clone)]
+struct Position {
+    ending: Ending,
+    turn: Player,
+    white_king: usize,
+    black_king: usize,
+    pieces: Vec<usize>,
+}
+
+impl Position {
+    /// Reads a board with this ending's material, turning it around if Black has the pieces.
+    fn from_board(ending: Ending, board: &Board) -> Option<Position> {
+        let keeper = board.get_keeper();
+        if [Player::White, Player::Black].into_iter().any(|player| keeper.can_castle(Castle::Short, player) || keeper.can_castle(Castle::Long, player)) {
+            return None;
+        }
+        let (white, black) = (keeper.get_white_pieces(), keeper.get_black_pieces());
+        let (strong, weak, turned) = match (white.len(), black.len()) {
+            (_, 1) => (white, black, false),
+            (1, _) => (black, white, true),
+            _ => { return None; },
+        };
+
+        let king = |pieces: &[(PieceType, usize)]| pieces.iter().find(|(piece, _)| matches!(piece, PieceType::King)).map(|(_, square)| *square);
+        // Turning the board around swaps the colors and flips the ranks.
+        let orient = |square: usize| if turned { (ROW_LEN - 1 - square / ROW_LEN) * ROW_LEN + square % ROW_LEN } else { square };
+
+        let mut pieces = Vec::<usize>::new();
+        for kind in ending.pieces() {
+            let square = strong.iter()
+                .find(|(piece, square)| std::mem::discriminant(piece) == std::mem::discriminant(kind) && !pieces.contains(&orient(*square)))
+                .map(|(_, square)| orient(*square))?;
+            pieces.push(square);
+        }
+        if strong.len() != ending.pieces().len() + 1 {
+            return None;
+        }
+
+        let turn = if turned { board.get_turn().opponent() } else { board.get_turn() };
+        let position = Position { ending, turn, white_king: orient(king(&strong)?), black_king: orient(king(&weak)?), pieces };
+        Some(position.canonical())
+    }
+
+    /// Uses the board's symmetries to bring the white king into the squares the table indexes.
+    fn canonical(mut self) -> Position {
+        let file = |square: usize| square % ROW_LEN;
+        let rank = |square: usize| ROW_LEN - 1 - square / ROW_LEN;
+
+        let mut transforms = Vec::<fn(usize) -> usize>::new();
+        if file(self.white_king) > 3 {
+            transforms.push(mirror_file);
+        }
+        if !self.ending.has_pawn() && rank(self.white_king) > 3 {
+            transforms.push(mirror_rank);
+        }
+        for transform in &transforms {
+            self.apply(*transform);
+        }
+        if !self.ending.has_pawn() && rank(self.white_king) > file(self.white_king) {
+            self.apply(transpose);
+        }
+        self
+    }
+
+    fn apply(&mut self, transform: fn(usize) -> usize) {
+        self.white_king = transform(self.white_king);
+        self.black_king = transform(self.black_king);
+        for square in &mut self.pieces {
+            *square = transform(*square);
+        }
+    }
+
+    fn encode(&self) -> usize {
+        let file = self.white_king % ROW_LEN;
+        let rank = ROW_LEN - 1 - self.white_king / ROW_LEN;
+        let slot = if self.ending.has_pawn() {
+            rank * 4 + file
+        } else {
+            // a1 b1 c1 d1 b2 c2 d2 c3 d3 d4
+            [0, 1, 2, 3, 0, 4, 5, 6, 0, 0, 7, 8, 0, 0, 0, 9][rank * 4 + file]
+        };
+        let mut index = slot;
+        index = index * BOARD_LEN + self.black_king;
+        for square in &self.pieces {
+            index = index * BOARD_LEN + square;
+        }
+        index * 2 + matches!(self.turn, Player::Black) as usize
+    }
+
+    /// The position with this index, if it is a placement the table uses (no two pieces on one
+    /// square, the white king on its slots, no pawn on the first or last rank).
+    fn decode(ending: Ending, mut index: usize) -> Option<Position> {
+        let turn = if index.is_multiple_of(2) { Player::White } else { Player::Black };
+        index /= 2;
+        let mut pieces = vec![0; ending.pieces().len()];
+        for square in pieces.iter_mut().rev() {
+            *square = index % BOARD_LEN;
+            index /= BOARD_LEN;
+        }
+        let black_king = index % BOARD_LEN;
+        let slot = index / BOARD_LEN;
+
+        let (file, rank) = if ending.has_pawn() {
+            (slot % 4, slot / 4)
+        } else {
+            [(0, 0), (1, 0), (2, 0), (3, 0), (1, 1), (2, 1), (3, 1), (2, 2), (3, 2), (3, 3)][slot]
+        };
+        let white_king = (ROW_LEN - 1 - rank) * ROW_LEN + file;
+
+        let mut squares = vec![white_king, black_king];
+        squares.extend(&pieces);
+        for (i, square) in squares.iter().enumerate() {
+            if squares[..i].contains(square) { return None; }
+        }
+        if ending.has_pawn() && pieces.iter().any(|square| *square < ROW_LEN || *square >= BOARD_LEN - ROW_LEN) {
+            return None;
+        }
+        Some(Position { ending, turn, white_king, black_king, pieces })
+    }
+
+    fn board(&self) -> Board {
+        let mut spaces = [Piece::None; BOARD_LEN];
+        spaces[self.white_king] = Piece::Piece(PlayerPiece::new(PieceType::King, Player::White));
+        spaces[self.black_king] = Piece::Piece(PlayerPiece::new(PieceType::King, Player::Black));
+        for (square, piece) in self.pieces.iter().zip(self.ending.pieces()) {
+            spaces[*square] = Piece::Piece(PlayerPiece::new(*piece, Player::White));
+        }
+        let mut keeper = Keeper::new(&spaces);
+        keeper.set_castling(Player::White, false, false);
+        keeper.set_castling(Player::Black, false, false);
+        Board::setup(spaces, self.turn, keeper, None, 0, 1)
+    }
+
+    /// Where a legal move from this position leads. Promotions are looked up in `built`.
+    fn after(&self, m: &Move, built: &[DtmTable]) -> After {
+        let mut child = self.clone();
+        child.turn = self.turn.opponent();
+        if m.from == self.white_king {
+            child.white_king = m.to;
+        } else if m.from == self.black_king {
+            // The bare king taking a piece leaves too little to mate with.
+            if self.pieces.contains(&m.to) {
+                return After::Outside(DRAW);
+            }
+            child.black_king = m.to;
+        } else if let Some(i) = self.pieces.iter().position(|square| *square == m.from) {
+            child.pieces[i] = m.to;
+        }
+
+        child.ending = match m.promotion {
+            Some(PieceType::Queen) => Ending::Kqk,
+            Some(PieceType::Rook) => Ending::Krk,
+            Some(_) => { return After::Outside(DRAW); },
+            None => { return After::Inside(child.canonical()); },
+        };
+        let child = child.canonical();
+        let table = built.iter().find(|table| table.ending == child.ending).expect("tables are built in order");
+        After::Outside(table.value(&child))
+    }
+}
+
+fn mirror_file(square: usize) -> usize {
+    square - square % ROW_LEN + (ROW_LEN - 1 - square % ROW_LEN)
+}
+
+fn mirror_rank(square: usize) -> usize {
+    (ROW_LEN - 1 - square / ROW_LEN) * ROW_LEN + square % ROW_LEN
+}
+
+/// Swaps files and ranks, reflecting in the a1-h8 diagonal.
+fn transpose(square: usize) -> usize {
+    let (file, rank) = (square % ROW_LEN, ROW_LEN - 1 - square / ROW_LEN);
+    (ROW_LEN - 1 - file) * ROW_LEN + rank
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::OnceLock;
+    use super::*;
+
+    /// KQK, KRK and KPK, built once for all the tests.
+    fn tables() -> &'static [DtmTable] {
+        static TABLES: OnceLock<Vec<DtmTable>> = OnceLock::new();
+        TABLES.get_or_init(|| {
+            let mut built = Vec::new();
+            for ending in [Ending::Kqk, Ending::Krk, Ending::Kpk] {
+                let table = DtmTable::generate(ending, &built).unwrap();
+                built.push(table);
+            }
+            built
+        })
+    }
+
+    fn table(ending: Ending) -> &'static DtmTable {
+        tables().iter().find(|table| table.ending() == ending).unwrap()
+    }
+
+    fn probe(fen: &str) -> Option<Dtm> {
+        let board = Board::from_fen(fen).unwrap();
+        tables().iter().find_map(|table| table.probe(&board))
+    }
+
+    #[test]
+    fn longest_wins() {
+        let (kqk, _) = table(Ending::Kqk).longest_win().unwrap();
+        let (krk, fen) = table(Ending::Krk).longest_win().unwrap();
+        assert_eq!(kqk.moves(), 10);
+        assert_eq!(krk.moves(), 16);
+        assert_eq!(probe(&fen), Some(krk));
+    }
+
+    #[test]
+    fn known_positions() {
+        // Mated on the board, and mate in one.
+        assert_eq!(probe("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Loss(0)));
+        assert_eq!(probe("k7/8/1K6/8/8/8/7Q/8 w - - 0 1"), Some(Dtm::Win(1)));
+        assert_eq!(probe("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(Dtm::Draw));
+        // The rook hangs next to the king.
+        assert_eq!(probe("8/8/8/8/8/8/Rk6/7K b - - 0 1"), Some(Dtm::Draw));
+        // Black has the queen: the board is turned around for the lookup.
+        assert_eq!(probe("8/8/8/8/8/1k6/8/K1q5 w - - 0 1"), Some(Dtm::Loss(0)));
+        // The king in front of its pawn wins with the opposition, and only with it, unless it has
+        // reached the sixth rank.
+        assert!(matches!(probe("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"), Some(Dtm::Loss(_))));
+        assert_eq!(probe("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(Dtm::Draw));
+        assert!(matches!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"), Some(Dtm::Win(_))));
+        assert_eq!(probe("8/8/8/8/8/4k3/4P3/4K3 w - - 0 1"), Some(Dtm::Draw));
+        // Not one of the endings.
+        assert_eq!(probe("4k3/8/8/8/8/8/8/3QK2R w - - 0 1"), None);
+    }
+
+    #[test]
+    fn best_move_mates() {
+        let tables = DtmTables { tables: vec![DtmTable::from_bytes(&table(Ending::Kqk).to_bytes()).unwrap()] };
+        let board = Board::from_fen("k7/8/1K6/8/8/8/7Q/8 w - - 0 1").unwrap();
+        let (m, dtm) = tables.best_move(&board).unwrap();
+        assert_eq!(dtm, Dtm::Win(1));
+        let mut after = board.clone();
+        after.play(m).unwrap();
+        after.next_turn();
+        assert_eq!(tables.probe(&after), Some(Dtm::Loss(0)));
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let dir = std::env::temp_dir().join(format!("chess-dtm-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for ending in [Ending::Kqk, Ending::Krk] {
+            table(ending).save(dir.join(format!("{}.dtm", ending.name()))).unwrap();
+        }
+        let loaded = DtmTables::open(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        let loaded = loaded.unwrap();
+
+        for fen in ["k7/8/1K6/8/8/8/7Q/8 w - - 0 1", "8/8/8/8/8/8/Rk6/7K b - - 0 1", "8/8/3k4/8/8/8/8/R3K3 b - - 0 1"] {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(loaded.probe(&board), probe(fen), "{}", fen);
+        }
+        assert_eq!(loaded.tables.iter().map(|table| table.counts()).collect::<Vec<_>>(),
+            [Ending::Kqk, Ending::Krk].map(|ending| table(ending).counts()));
+
+        let bytes = table(Ending::Krk).to_bytes();
+        assert!(DtmTable::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(DtmTable::from_bytes(b"DTM2").is_err());
+    }
+}
@@ -0,0 +1,387 @@
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    thread,
+    time::Instant,
+};
+use super::{
+    board::Board,
+    book::{Book, Random, Selection},
+    checker::{is_in_check, Snapshot},
+    eval::{evaluate, piece_value},
+    movement::legal_moves,
+    see::see,
+    timeman::TimeManager,
+    tt::{Bound, TranspositionTable},
+    utils::{Move, Piece},
+    zobrist::polyglot_key,
+};
+
+/// Table size for searches that don't keep one between calls.
+const SCRATCH_HASH_MB: usize = 4;
+/// Nodes searched between looks at the clock, and between a helper thread adding its nodes to the count.
+const CLOCK_CHECK_NODES: u64 = 1024;
+
+/// Deeper than any search gets; the depth for searches that only time or `stop` ends.
+pub const MAX_DEPTH: u32 = 64;
+
+/// Score of a checkmate. Mates found further away score slightly lower so the shortest one is played.
+pub const MATE: i32 = 100_000;
+
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    /// Centipawns from the point of view of the side to move.
+    pub score: i32,
+    pub nodes: u64,
+    /// The line the search expects, starting with the best move.
+    pub pv: Vec<Move>,
+}
+
+/// Plays from the opening book while it has an answer, and searches once it runs out; `pondered`
+/// is a search of this position already done while pondering, used instead of a new one. Gives the
+/// move with the reply the search expects to it, if it came from a search.
+pub fn choose_move(board: &Board, depth: u32, book: Option<&Book>, pondered: Option<SearchResult>) -> Option<(Move, Option<Move>)> {
+    if let Some(m) = book.and_then(|book| book.pick(board, Selection::Weighted, &mut Random::from_clock())) {
+        return Some((m, None));
+    }
+    let result = pondered.unwrap_or_else(|| search(board, depth));
+    Some((result.best_move?, result.pv.get(1).copied()))
+}
+
+/// One finished depth of `search_lines`.
+pub struct Analysis {
+    pub depth: u32,
+    /// The best moves found, best first, each with its score and line.
+    pub lines: Vec<SearchResult>,
+    /// Nodes searched for this depth and the ones before it.
+    pub nodes: u64,
+}
+
+/// Searches the position to a fixed depth and returns the best move found.
+pub fn search(board: &Board, depth: u32) -> SearchResult {
+    search_with(board, depth, &TranspositionTable::new(SCRATCH_HASH_MB))
+}
+
+/// `search` with a transposition table that outlives it, so later searches reuse what it found.
+pub fn search_with(board: &Board, depth: u32, tt: &TranspositionTable) -> SearchResult {
+    tt.new_search();
+    // Each depth leaves best moves in the table for the next one to try first.
+    let mut context = Context::new(tt);
+    let mut result = None;
+    for current in 1..=depth.max(1) {
+        result = search_excluding(board, current, &[], &mut context);
+    }
+    let mut result = result.expect("a search nothing stops always finishes");
+    result.nodes = context.nodes;
+    result
+}
+
+/// When `search_lines` ends, besides running out of moves.
+pub struct Limits<'a> {
+    /// The deepest depth searched.
+    pub depth: u32,
+    /// The time the move may take, for games on a clock.
+    pub time: Option<TimeManager>,
+    /// Set from outside to end the search, e.g. for UCI `stop`.
+    pub stop: Option<&'a AtomicBool>,
+}
+
+impl Limits<'_> {
+    /// Searches to `depth`, however long it takes.
+    pub fn depth(depth: u32) -> Self {
+        Limits { depth, time: None, stop: None }
+    }
+}
+
+/// Searches to depth 1, 2 and so on up to the limits, keeping the `multipv` best moves at each:
+/// each line is found by searching again with the moves of the lines above it left out. `report` is
+/// called after every depth.
+///
+/// A depth cut short by the clock or the stop flag is thrown away, so the lines always come from the
+/// last finished depth. Depth 1 always finishes, so there is a move to play.
+///
+/// With more than one thread, the others search the same position alongside, half of them a depth
+/// ahead, and share what they find through the table (Lazy SMP); the lines still come from the
+/// first thread, but it finds them sooner. One thread searches alone and always the same way.
+pub fn search_lines(board: &Board, mut limits: Limits, multipv: usize, threads: usize, tt: &TranspositionTable, mut report: impl FnMut(&Analysis)) -> Analysis {
+    tt.new_search();
+    let stop = AtomicBool::new(false);
+    let helper_nodes = AtomicU64::new(0);
+    let depth = limits.depth.max(1);
+
+    thread::scope(|scope| {
+        for helper in 1..threads.max(1) {
+            let (stop, helper_nodes) = (&stop, &helper_nodes);
+            scope.spawn(move || {
+                let mut context = Context { stop: Some(stop), shared_nodes: Some(helper_nodes), ..Context::new(tt) };
+                let mut current = 1 + (helper % 2) as u32;
+                while current <= depth + 1 && search_excluding(board, current, &[], &mut context).is_some() {
+                    current += 1;
+                }
+                context.publish_nodes();
+            });
+        }
+
+        let mut context = Context::new(tt);
+        let mut analysis = Analysis { depth: 0, lines: Vec::new(), nodes: 0 };
+        'deepening: for current in 1..=depth {
+            let mut lines = Vec::<SearchResult>::new();
+            let mut excluded = Vec::<Move>::new();
+            while lines.len() < multipv.max(1) {
+                let Some(result) = search_excluding(board, current, &excluded, &mut context) else { break 'deepening; };
+                let Some(m) = result.best_move else { break; };
+                excluded.push(m);
+                lines.push(result);
+            }
+            analysis.depth = current;
+            analysis.lines = lines;
+            analysis.nodes = context.nodes + helper_nodes.load(Ordering::Relaxed);
+            report(&analysis);
+
+            if let Some(time) = &mut limits.time {
+                let best = analysis.lines.first();
+                if !time.next_depth(time.elapsed(), best.and_then(|line| line.best_move), best.map_or(0, |line| line.score)) {
+                    break;
+                }
+                context.deadline = Some(time.deadline());
+            }
+            context.stop = limits.stop;
+        }
+
+        stop.store(true, Ordering::Relaxed);
+        analysis
+    })
+}
+
+/// What one search carries down the tree.
+struct Context<'a> {
+    tt: &'a TranspositionTable,
+    nodes: u64,
+    /// Set from outside when the search should give up.
+    stop: Option<&'a AtomicBool>,
+    /// When the search has to give up by.
+    deadline: Option<Instant>,
+    /// The node count the clock was last looked at; it is only looked at every so many nodes.
+    checked_at: u64,
+    /// Where a helper thread adds its nodes as it goes, for the main thread to report.
+    shared_nodes: Option<&'a AtomicU64>,
+    /// The nodes already added there.
+    published: u64,
+    /// Once set, what the search returns is meaningless.
+    stopped: bool,
+}
+
+impl<'a> Context<'a> {
+    fn new(tt: &'a TranspositionTable) -> Self {
+        Context { tt, nodes: 0, stop: None, deadline: None, checked_at: 0, shared_nodes: None, published: 0, stopped: false }
+    }
+
+    fn publish_nodes(&mut self) {
+        if let Some(shared) = self.shared_nodes {
+            shared.fetch_add(self.nodes - self.published, Ordering::Relaxed);
+            self.published = self.nodes;
+        }
+    }
+
+    fn stopped(&mut self) -> bool {
+        if !self.stopped {
+            self.stopped = self.stop.is_some_and(|stop| stop.load(Ordering::Relaxed));
+        }
+        if !self.stopped && self.nodes - self.checked_at >= CLOCK_CHECK_NODES {
+            self.checked_at = self.nodes;
+            self.stopped = self.deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            self.publish_nodes();
+        }
+        self.stopped
+    }
+}
+
+/// `search` with some root moves left out. Once every move is left out there is no best move.
+/// Gives nothing when the search was stopped before it finished.
+fn search_excluding(board: &Board, depth: u32, excluded: &[Move], context: &mut Context) -> Option<SearchResult> {
+    let nodes = context.nodes;
+    let mut best_move = None;
+    let mut alpha = -MATE - 1;
+    let beta = MATE + 1;
+
+    let key = polyglot_key(board);
+    let moves = ordered_moves(board, context.tt.probe(key).and_then(|entry| entry.best_move));
+    if moves.is_empty() {
+        context.nodes += 1;
+        let score = if is_in_check(&Snapshot::new(board), board.get_turn()) { -MATE } else { 0 };
+        return Some(SearchResult { best_move: None, score, nodes: 1, pv: Vec::new() });
+    }
+
+    let mut pv = Vec::<Move>::new();
+    for m in moves.into_iter().filter(|m| !excluded.contains(m)) {
+        let mut child = board.clone();
+        if child.play(m).is_err() { continue; }
+        child.next_turn();
+
+        let mut line = Vec::<Move>::new();
+        let score = -negamax(&child, depth.saturating_sub(1), -beta, -alpha, 1, &mut line, context);
+        if context.stopped() {
+            return None;
+        }
+        if best_move.is_none() || score > alpha {
+            alpha = score;
+            best_move = Some(m);
+            pv = vec![m];
+            pv.append(&mut line);
+        }
+    }
+
+    // With moves left out, the score is only the best of the rest, not the position's.
+    if excluded.is_empty() && best_move.is_some() {
+        context.tt.store(key, depth, Bound::Exact, alpha, best_move, 0);
+    }
+    Some(SearchResult { best_move, score: alpha, nodes: context.nodes - nodes, pv })
+}
+
+fn negamax(board: &Board, depth: u32, mut alpha: i32, beta: i32, ply: i32, pv: &mut Vec<Move>, context: &mut Context) -> i32 {
+    context.nodes += 1;
+    if context.stopped() {
+        return 0;
+    }
+
+    if depth == 0 {
+        return quiesce(board, alpha, beta, &mut context.nodes);
+    }
+
+    let key = polyglot_key(board);
+    let entry = context.tt.probe(key);
+    if let Some(entry) = entry.filter(|entry| entry.depth >= depth) {
+        let score = entry.score(ply);
+        let cutoff = match entry.bound {
+            Bound::Exact => Some(score.clamp(alpha, beta)),
+            Bound::Lower if score >= beta => Some(beta),
+            Bound::Upper if score <= alpha => Some(alpha),
+            _ => None,
+        };
+        if let Some(score) = cutoff {
+            // Only an exact score can become part of the line above, so only then is it rebuilt.
+            pv.clear();
+            if entry.bound == Bound::Exact {
+                pv.extend(table_line(board, context.tt, depth));
+            }
+            return score;
+        }
+    }
+
+    let moves = ordered_moves(board, entry.and_then(|entry| entry.best_move));
+    if moves.is_empty() {
+        if is_in_check(&Snapshot::new(board), board.get_turn()) {
+            return -MATE + ply;
+        }
+        return 0;
+    }
+
+    let mut best_move = None;
+    let mut bound = Bound::Upper;
+    for m in moves {
+        let mut child = board.clone();
+        if child.play(m).is_err() { continue; }
+        child.next_turn();
+
+        let mut line = Vec::<Move>::new();
+        let score = -negamax(&child, depth - 1, -beta, -alpha, ply + 1, &mut line, context);
+        if context.stopped() {
+            return 0;
+        }
+        if score >= beta {
+            context.tt.store(key, depth, Bound::Lower, beta, Some(m), ply);
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+            best_move = Some(m);
+            bound = Bound::Exact;
+            pv.clear();
+            pv.push(m);
+            pv.append(&mut line);
+        }
+    }
+
+    context.tt.store(key, depth, bound, alpha, best_move, ply);
+    alpha
+}
+
+/// The line the table holds from this position: its best move, then the best move stored for the
+/// position that leads to, and so on for at most `length` moves.
+fn table_line(board: &Board, tt: &TranspositionTable, length: u32) -> Vec<Move> {
+    let mut line = Vec::<Move>::new();
+    let mut board = board.clone();
+    while line.len() < length as usize {
+        let Some(m) = tt.probe(polyglot_key(&board)).and_then(|entry| entry.best_move) else { break; };
+        if board.play(m).is_err() { break; }
+        board.next_turn();
+        line.push(m);
+    }
+    line
+}
+
+/// Keeps searching captures past the depth limit so the score isn't taken in the middle of an exchange.
+fn quiesce(board: &Board, mut alpha: i32, beta: i32, nodes: &mut u64) -> i32 {
+    let stand_pat = evaluate(board);
+    if stand_pat >= beta {
+        return beta;
+    }
+    if stand_pat > alpha {
+        alpha = stand_pat;
+    }
+
+    for m in ordered_moves(board, None) {
+        // Captures that lose material in the exchange are left out; they can't raise the score.
+        if !is_capture(board, &m) || see(board, &m) < 0 { continue; }
+
+        *nodes += 1;
+        let mut child = board.clone();
+        if child.play(m).is_err() { continue; }
+        child.next_turn();
+
+        let score = -quiesce(&child, -beta, -alpha, nodes);
+        if score >= beta {
+            return beta;
+        }
+        if score > alpha {
+            alpha = score;
+        }
+    }
+
+    alpha
+}
+
+/// Legal moves with the captures that win material first, best first by static exchange, then the
+/// quiet moves and last the captures that lose material, so alpha-beta cuts off sooner. The move
+/// the transposition table remembers, if any, goes before all of them.
+fn ordered_moves(board: &Board, hash_move: Option<Move>) -> Vec<Move> {
+    let mut moves = legal_moves(board);
+    moves.sort_by_cached_key(|m| {
+        if hash_move == Some(*m) {
+            return i32::MIN;
+        }
+        let promotion = m.promotion.map(piece_value).unwrap_or(0);
+        let score = if is_capture(board, m) {
+            let gain = see(board, m) + promotion;
+            if gain >= 0 { 10_000 + gain } else { gain }
+        } else {
+            promotion
+        };
+        -score
+    });
+    moves
+}
+
+fn is_capture(board: &Board, m: &Move) -> bool {
+    matches!(board.get_space(m.to), Some(Piece::Piece(_))) && m.castle.is_none()
+}
+
+/// Formats a score for display, e.g. `+0.35` or `#3` for mate in three.
+pub fn format_score(score: i32) -> String {
+    if score.abs() > MATE - 1000 {
+        let plies = MATE - score.abs();
+        let moves = (plies + 1) / 2;
+        if score > 0 { format!("#{}", moves) } else { format!("#-{}", moves) }
+    } else {
+        format!("{:+.2}", score as f64 / 100.0)
+    }
+}
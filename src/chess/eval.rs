@@ -0,0 +1,138 @@
+use super::{
+    board::{Board, BOARD_LEN, ROW_LEN},
+    pawns::{pawn_structure, PawnStructure},
+    utils::{Piece, PieceType, Player},
+};
+
+// Piece-square tables from White's side, indexed like the board (a8 is 0).
+// Black looks them up mirrored.
+const PAWN_TABLE: [i32; BOARD_LEN] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+const KNIGHT_TABLE: [i32; BOARD_LEN] = [
+   -50,-40,-30,-30,-30,-30,-40,-50,
+   -40,-20,  0,  0,  0,  0,-20,-40,
+   -30,  0, 10, 15, 15, 10,  0,-30,
+   -30,  5, 15, 20, 20, 15,  5,-30,
+   -30,  0, 15, 20, 20, 15,  0,-30,
+   -30,  5, 10, 15, 15, 10,  5,-30,
+   -40,-20,  0,  5,  5,  0,-20,-40,
+   -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+const BISHOP_TABLE: [i32; BOARD_LEN] = [
+   -20,-10,-10,-10,-10,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5, 10, 10,  5,  0,-10,
+   -10,  5,  5, 10, 10,  5,  5,-10,
+   -10,  0, 10, 10, 10, 10,  0,-10,
+   -10, 10, 10, 10, 10, 10, 10,-10,
+   -10,  5,  0,  0,  0,  0,  5,-10,
+   -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+const ROOK_TABLE: [i32; BOARD_LEN] = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+const QUEEN_TABLE: [i32; BOARD_LEN] = [
+   -20,-10,-10, -5, -5,-10,-10,-20,
+   -10,  0,  0,  0,  0,  0,  0,-10,
+   -10,  0,  5,  5,  5,  5,  0,-10,
+    -5,  0,  5,  5,  5,  5,  0, -5,
+     0,  0,  5,  5,  5,  5,  0, -5,
+   -10,  5,  5,  5,  5,  5,  0,-10,
+   -10,  0,  5,  0,  0,  0,  0,-10,
+   -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+const KING_TABLE: [i32; BOARD_LEN] = [
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -30,-40,-40,-50,-50,-40,-40,-30,
+   -20,-30,-30,-40,-40,-30,-30,-20,
+   -10,-20,-20,-20,-20,-20,-20,-10,
+    20, 20,  0,  0,  0,  0, 20, 20,
+    20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+/// Bonus for a rook on a file without pawns, and on one without its own side's pawns.
+const ROOK_OPEN_FILE: i32 = 15;
+const ROOK_HALF_OPEN_FILE: i32 = 8;
+
+/// Value of a piece in centipawns.
+pub fn piece_value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::Pawn => 100,
+        PieceType::Knight => 320,
+        PieceType::Bishop => 330,
+        PieceType::Rook => 500,
+        PieceType::Queen => 900,
+        PieceType::King => 0,
+    }
+}
+
+fn square_value(piece: PieceType, index: usize) -> i32 {
+    match piece {
+        PieceType::Pawn => PAWN_TABLE[index],
+        PieceType::Knight => KNIGHT_TABLE[index],
+        PieceType::Bishop => BISHOP_TABLE[index],
+        PieceType::Rook => ROOK_TABLE[index],
+        PieceType::Queen => QUEEN_TABLE[index],
+        PieceType::King => KING_TABLE[index],
+    }
+}
+
+/// Evaluates the position in centipawns from White's point of view.
+pub fn evaluate_white(board: &Board) -> i32 {
+    let pawns = pawn_structure(board);
+    let mut score = pawns.score();
+    for (i, piece) in board.get_spaces().iter().enumerate() {
+        if let Piece::Piece(p) = piece {
+            match p.player {
+                Player::White => { score += piece_value(p.piece) + square_value(p.piece, i) + file_value(p.piece, i, p.player, &pawns); },
+                Player::Black => { score -= piece_value(p.piece) + square_value(p.piece, i ^ 56) + file_value(p.piece, i, p.player, &pawns); },
+            }
+        }
+    }
+    score
+}
+
+/// What a rook gains from the pawns on its file.
+fn file_value(piece: PieceType, index: usize, player: Player, pawns: &PawnStructure) -> i32 {
+    if !matches!(piece, PieceType::Rook) {
+        return 0;
+    }
+    let file = index % ROW_LEN;
+    if pawns.open_files.contains(&file) {
+        ROOK_OPEN_FILE
+    } else if pawns.side(player).half_open_files.contains(&file) {
+        ROOK_HALF_OPEN_FILE
+    } else {
+        0
+    }
+}
+
+/// Evaluates the position in centipawns from the point of view of the side to move.
+pub fn evaluate(board: &Board) -> i32 {
+    match board.get_turn() {
+        Player::White => evaluate_white(board),
+        Player::Black => -evaluate_white(board),
+    }
+}
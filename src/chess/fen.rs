@@ -0,0 +1,167 @@
+use anyhow::{anyhow, Result};
+use super::{
+    board::{Board, BOARD_LEN, ROW_LEN},
+    checker::{is_in_check, Snapshot},
+    interpret::parse_square,
+    keeper::Keeper,
+    san::square_name,
+    utils::{Castle, Piece, PieceType, Player, PlayerPiece},
+};
+
+impl Board {
+    /// Reads a position in Forsyth-Edwards Notation. The move counters may be left off.
+    pub fn from_fen(fen: &str) -> Result<Board> {
+        let fields: Vec<_> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(anyhow!("A FEN needs placement, side to move, castling and en passant fields!"));
+        }
+
+        let rows: Vec<_> = fields[0].split('/').collect();
+        if rows.len() != ROW_LEN {
+            return Err(anyhow!("A FEN needs 8 ranks!"));
+        }
+
+        let mut spaces = [Piece::None; BOARD_LEN];
+        for (row, text) in rows.iter().enumerate() {
+            let mut col = 0;
+            for c in text.chars() {
+                if let Some(skip) = c.to_digit(10) {
+                    col += skip as usize;
+                    continue;
+                }
+                if col >= ROW_LEN {
+                    return Err(anyhow!("Rank {} of the FEN is too long!", 8 - row));
+                }
+                spaces[(row * ROW_LEN) + col] = Piece::Piece(piece_from_char(c)?);
+                col += 1;
+            }
+            if col != ROW_LEN {
+                return Err(anyhow!("Rank {} of the FEN does not have 8 squares!", 8 - row));
+            }
+        }
+
+        for player in [Player::White, Player::Black] {
+            let kings = spaces.iter().filter(|p| matches!(p, Piece::Piece(PlayerPiece { piece: PieceType::King, player: owner }) if std::mem::discriminant(owner) == std::mem::discriminant(&player))).count();
+            if kings != 1 {
+                return Err(anyhow!("Each side needs exactly one king!"));
+            }
+        }
+
+        let turn = match fields[1] {
+            "w" => Player::White,
+            "b" => Player::Black,
+            _ => { return Err(anyhow!("The side to move must be 'w' or 'b'!")); },
+        };
+        // The side that just moved can't have left its king in check; the king could be taken.
+        let waiting = turn.opponent();
+        if is_in_check(&Snapshot::from_spaces(spaces), waiting) {
+            return Err(anyhow!("The side not to move is in check!"));
+        }
+
+        let mut keeper = Keeper::new(&spaces);
+        let castling = fields[2];
+        if castling != "-" && castling.chars().any(|c| !"KQkq".contains(c)) {
+            return Err(anyhow!("Invalid castling field in FEN!"));
+        }
+        keeper.set_castling(Player::White, castling.contains('K'), castling.contains('Q'));
+        keeper.set_castling(Player::Black, castling.contains('k'), castling.contains('q'));
+        // Drops any right whose king or rook isn't on its square.
+        keeper.update(&spaces);
+
+        let en_passant = match fields[3] {
+            "-" => None,
+            square => Some(parse_square(square)?),
+        };
+        // The square skipped by a pawn that just moved two: rank 6 for White to take, 3 for Black.
+        let en_passant_row = match turn {
+            Player::White => 2,
+            Player::Black => 5,
+        };
+        if en_passant.is_some_and(|square| square / ROW_LEN != en_passant_row) {
+            return Err(anyhow!("The en passant square must be on rank 6 with White to move, or 3 with Black!"));
+        }
+
+        let halfmove_clock = match fields.get(4) {
+            Some(n) => n.parse().map_err(|_| anyhow!("Invalid halfmove clock in FEN!"))?,
+            None => 0,
+        };
+        let fullmove_number = match fields.get(5) {
+            Some(n) => n.parse().map_err(|_| anyhow!("Invalid move number in FEN!"))?,
+            None => 1,
+        };
+
+        Ok(Board::setup(spaces, turn, keeper, en_passant, halfmove_clock, fullmove_number))
+    }
+
+    pub fn to_fen(&self) -> String {
+        let spaces = self.get_spaces();
+        let mut fen = String::new();
+
+        for row in 0..ROW_LEN {
+            let mut empty = 0;
+            for col in 0..ROW_LEN {
+                match spaces[(row * ROW_LEN) + col] {
+                    Piece::None => { empty += 1; },
+                    Piece::Piece(p) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece_to_char(p));
+                    },
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if row < ROW_LEN - 1 {
+                fen.push('/');
+            }
+        }
+
+        fen.push_str(match self.get_turn() {
+            Player::White => " w ",
+            Player::Black => " b ",
+        });
+
+        let keeper = self.get_keeper();
+        let mut castling = String::new();
+        if keeper.can_castle(Castle::Short, Player::White) { castling.push('K'); }
+        if keeper.can_castle(Castle::Long, Player::White) { castling.push('Q'); }
+        if keeper.can_castle(Castle::Short, Player::Black) { castling.push('k'); }
+        if keeper.can_castle(Castle::Long, Player::Black) { castling.push('q'); }
+        if castling.is_empty() { castling.push('-'); }
+        fen.push_str(&castling);
+
+        match self.get_en_passant() {
+            Some(square) => fen.push_str(&format!(" {}", square_name(square))),
+            None => fen.push_str(" -"),
+        }
+
+        fen.push_str(&format!(" {} {}", self.get_halfmove_clock(), self.get_fullmove_number()));
+
+        fen
+    }
+}
+
+fn piece_from_char(c: char) -> Result<PlayerPiece> {
+    let player = if c.is_ascii_uppercase() { Player::White } else { Player::Black };
+    let piece = match c.to_ascii_lowercase() {
+        'p' => PieceType::Pawn,
+        'r' => PieceType::Rook,
+        'n' => PieceType::Knight,
+        'b' => PieceType::Bishop,
+        'q' => PieceType::Queen,
+        'k' => PieceType::King,
+        _ => { return Err(anyhow!("Unknown piece '{}' in FEN!", c)); },
+    };
+    Ok(PlayerPiece::new(piece, player))
+}
+
+/// The FEN letter of a piece: upper case for White, lower case for Black.
+pub fn piece_to_char(p: PlayerPiece) -> char {
+    match p.player {
+        Player::White => p.to_char(),
+        Player::Black => p.to_char().to_ascii_lowercase(),
+    }
+}
@@ -0,0 +1,159 @@
+use std::{fs, path::Path, time::Duration};
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::Board,
+    book::Book,
+    broadcast::Broadcaster,
+    interpret::parse_uci,
+    ponder::Ponder,
+    san::to_uci,
+    utils::Player,
+};
+
+pub const AUTOSAVE_FILE: &str = "autosave.chess";
+
+const SAVE_HEADER: &str = "[chess save 1]";
+
+/// The computer player in a game against the engine.
+#[derive(Clone, Copy)]
+pub struct Opponent {
+    pub side: Player,
+    pub depth: u32,
+    /// Thinks on the human's time; see `ponder`.
+    pub ponder: bool,
+}
+
+/// A game in progress: the board plus what the board itself doesn't track.
+pub struct Game {
+    pub board: Board,
+    pub white_name: String,
+    pub black_name: String,
+    /// Time each player has spent thinking.
+    pub white_clock: Duration,
+    pub black_clock: Duration,
+    /// Set when one side is played by the engine. Not part of the save file.
+    pub opponent: Option<Opponent>,
+    /// Set when spectators are watching. Not part of the save file.
+    pub broadcast: Option<Broadcaster>,
+    /// Opening book for the engine. Not part of the save file.
+    pub book: Option<Book>,
+    /// How many of the engine's best lines to show under the board; 0 when analysis is off.
+    /// Not part of the save file.
+    pub analysis_lines: usize,
+    /// The engine's search of the reply it expects, while the human thinks. Not part of the save file.
+    pub pondering: Option<Ponder>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Game::new(Board::new())
+    }
+}
+
+impl Game {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            white_name: "White".to_string(),
+            black_name: "Black".to_string(),
+            white_clock: Duration::ZERO,
+            black_clock: Duration::ZERO,
+            opponent: None,
+            broadcast: None,
+            book: None,
+            analysis_lines: 0,
+            pondering: None,
+        }
+    }
+
+    pub fn clock_mut(&mut self, player: Player) -> &mut Duration {
+        match player {
+            Player::White => &mut self.white_clock,
+            Player::Black => &mut self.black_clock,
+        }
+    }
+
+    /// Writes the game in the save format:
+    ///
+    /// ```text
+    /// [chess save 1]
+    /// White: <name>
+    /// Black: <name>
+    /// Start: <FEN of the starting position>
+    /// Clock: <white milliseconds> <black milliseconds>
+    /// Moves: <moves in coordinate notation, separated by spaces>
+    /// ```
+    pub fn to_save(&self) -> String {
+        let moves: Vec<_> = self.board.get_history().iter().map(to_uci).collect();
+        format!(
+            "{}\nWhite: {}\nBlack: {}\nStart: {}\nClock: {} {}\nMoves: {}\n",
+            SAVE_HEADER,
+            self.white_name,
+            self.black_name,
+            self.board.get_start_fen(),
+            self.white_clock.as_millis(),
+            self.black_clock.as_millis(),
+            moves.join(" "),
+        )
+    }
+
+    /// Reads a game written by `to_save`, replaying its moves from the starting position.
+    pub fn from_save(text: &str) -> Result<Game> {
+        let mut lines = text.lines();
+        if lines.next().map(|l| l.trim()) != Some(SAVE_HEADER) {
+            return Err(anyhow!("Not a chess save file!"));
+        }
+
+        let mut game = Game::default();
+        let mut moves = "";
+        for line in lines {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => { continue; },
+            };
+            match key {
+                "White" => { game.white_name = value.to_string(); },
+                "Black" => { game.black_name = value.to_string(); },
+                "Start" => { game.board = Board::from_fen(value)?; },
+                "Clock" => {
+                    let times: Vec<u64> = value.split_whitespace()
+                        .map(|t| t.parse::<u64>())
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| anyhow!("Invalid clock line in save file!"))?;
+                    if times.len() != 2 {
+                        return Err(anyhow!("Invalid clock line in save file!"));
+                    }
+                    game.white_clock = Duration::from_millis(times[0]);
+                    game.black_clock = Duration::from_millis(times[1]);
+                },
+                "Moves" => { moves = value; },
+                _ => { },
+            }
+        }
+
+        for text in moves.split_whitespace() {
+            let m = parse_uci(text, &game.board).with_context(|| format!("Could not replay move '{}'", text))?;
+            game.board.play(m)?;
+            game.board.next_turn();
+        }
+
+        Ok(game)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        fs::write(path.as_ref(), self.to_save())
+            .with_context(|| format!("Could not write {}", path.as_ref().display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Game> {
+        let text = fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Could not read {}", path.as_ref().display()))?;
+        Game::from_save(&text)
+    }
+}
+
+/// Formats a clock as minutes and seconds.
+pub fn format_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
@@ -2,12 +2,15 @@ use std::mem;
 
 use anyhow::{anyhow, Result};
 
-use super::{utils::{Move, PieceType, Player}, board::{ROW_LEN, Board}};
+use super::{utils::{Move, PieceType, Player}, board::{ROW_LEN, Board}, movement::legal_moves, san::parse_san};
 
+/// Reads a move typed by a player. Square coordinates may be written in any case, but SAN is
+/// passed through as typed so `Bxc5` and `bxc5` stay different moves.
 pub fn interpret_notation(input: &str, board: &Board) -> Result<Move> {
     let args: Vec<_> = input.split(' ').collect();
 
     if args.len() > 1 {
+        let args: Vec<_> = args.iter().map(|arg| arg.to_lowercase()).collect();
         let mut indices: [usize; 2] = [0; 2];
         for i in 0..2 {
             let mut it = args[i].chars();
@@ -52,7 +55,7 @@ fn complex_interpretation(input: &str, board: &Board) -> Result<Move> {
     let len = input.len();
 
     if len == 2 {
-        let col = it.next().unwrap_or('z');
+        let col = it.next().unwrap_or('z').to_ascii_lowercase();
         let row = it.next().unwrap_or('9');
         if col < 'a' { return Err(anyhow!("Invalid input!")); }
         if row > '8' { return Err(anyhow!("Invalid input!")); }
@@ -90,7 +93,11 @@ fn complex_interpretation(input: &str, board: &Board) -> Result<Move> {
         }
     }
 
-    Err(anyhow!("Not implemented yet! [from complex_interpretation]"))
+    if let Ok(m) = parse_uci(&input.to_lowercase(), board) {
+        return Ok(m);
+    }
+
+    parse_san(input, board)
 }
 
 fn get_pawns_in_column(col: usize, board: &Board) -> Vec<usize> {
@@ -126,7 +133,7 @@ fn generate_pawn_move(from: usize, to: usize, turn: Player) -> Option<Move> {
         },
         Player::Black => {
             if to <= from { return None; }
-            if (8..15).contains(&from) { // Pawn in home row
+            if (8..16).contains(&from) { // Pawn in home row
                 if to == from + ROW_LEN || to == from + (ROW_LEN * 2) {
                     return Some(Move::new(from, to));
                 }
@@ -140,3 +147,48 @@ fn generate_pawn_move(from: usize, to: usize, turn: Player) -> Option<Move> {
     None
 }
 
+
+/// Reads a square name such as `e4` into a board index.
+pub fn parse_square(input: &str) -> Result<usize> {
+    let mut it = input.chars();
+    let col = it.next().unwrap_or('z');
+    let row = it.next().unwrap_or('9');
+    if it.next().is_some() { return Err(anyhow!("Invalid square!")); }
+
+    if !('a'..='h').contains(&col) { return Err(anyhow!("Invalid square!")); }
+    if !('1'..='8').contains(&row) { return Err(anyhow!("Invalid square!")); }
+
+    let col = col as usize - 'a' as usize;
+    let row = '8' as usize - row as usize;
+
+    Ok((row * ROW_LEN) + col)
+}
+
+/// Reads a move written as its start and end squares (`e2e4`, `e1g1`, `e7e8q`) and
+/// matches it against the legal moves, so castles and promotions come out right.
+pub fn parse_uci(input: &str, board: &Board) -> Result<Move> {
+    let Move { from, to, promotion, .. } = Move::from_uci(input)?;
+
+    legal_moves(board).into_iter()
+        .find(|m| m.from == from && m.to == to && match (m.promotion, promotion) {
+            (None, None) => true,
+            (Some(a), Some(b)) => mem::discriminant(&a) == mem::discriminant(&b),
+            _ => false,
+        })
+        .ok_or_else(|| anyhow!("'{}' is not a legal move!", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piece_letters_keep_their_case() {
+        // Both the b4 pawn and the d4 bishop can take on c5.
+        let board = Board::from_fen("4k3/8/8/2p5/1P1B4/8/8/4K3 w - - 0 1").unwrap();
+        let bishop = interpret_notation("Bxc5", &board).unwrap();
+        let pawn = interpret_notation("bxc5", &board).unwrap();
+        assert_eq!((bishop.from, bishop.to), (parse_square("d4").unwrap(), parse_square("c5").unwrap()));
+        assert_eq!((pawn.from, pawn.to), (parse_square("b4").unwrap(), parse_square("c5").unwrap()));
+    }
+}
@@ -1,5 +1,5 @@
 use std::mem;
-use super::utils::{PieceType, Piece, Player, Castle};
+use super::utils::{PieceType, Piece, Player, PlayerPiece, Castle};
 
 const FULL_SET: [PieceType; 16] = [
     PieceType::Pawn, PieceType::Pawn,
@@ -12,19 +12,20 @@ const FULL_SET: [PieceType; 16] = [
     PieceType::Queen, PieceType::King,
 ];
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 enum CastleState {
     Unable,
     Able(CastleSide),
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 enum CastleSide {
     Both,
     Short,
     Long,
 }
 
+#[derive(Debug, Clone)]
 pub struct Keeper {
     white_pieces: Vec<(PieceType, usize)>,
     black_pieces: Vec<(PieceType, usize)>,
@@ -93,6 +94,20 @@ impl Keeper {
         pieces
     }
 
+    /// Sets which castles the player still has the right to, e.g. when reading a FEN.
+    pub fn set_castling(&mut self, turn: Player, short: bool, long: bool) {
+        let state = match (short, long) {
+            (true, true) => CastleState::Able(CastleSide::Both),
+            (true, false) => CastleState::Able(CastleSide::Short),
+            (false, true) => CastleState::Able(CastleSide::Long),
+            (false, false) => CastleState::Unable,
+        };
+        match turn {
+            Player::White => { self.white_castling = state; },
+            Player::Black => { self.black_castling = state; },
+        }
+    }
+
     pub fn get_black_pieces(&self) -> Vec<(PieceType, usize)> { self.black_pieces.clone() }
 
     pub fn get_white_pieces(&self) -> Vec<(PieceType, usize)> { self.white_pieces.clone() }
@@ -154,15 +169,15 @@ fn determine_castling_state(previous_state: (CastleState, CastleState), current:
     let mut black_can_castle_short = false;
 
     // Black check first
-    if let Some(PieceType::King) = current[4].extract_value() {
-        if let Some(PieceType::Rook) = current[0].extract_value() {
+    if let Piece::Piece(PlayerPiece { piece: PieceType::King, player: Player::Black }) = current[4] {
+        if let Piece::Piece(PlayerPiece { piece: PieceType::Rook, player: Player::Black }) = current[0] {
             match previous_state.1 {
                 CastleState::Able(CastleSide::Short) => { },
                 CastleState::Able(_) => { black_can_castle_long = true; },
                 _ => { },
             }
         }
-        if let Some(PieceType::Rook) = current[7].extract_value() {
+        if let Piece::Piece(PlayerPiece { piece: PieceType::Rook, player: Player::Black }) = current[7] {
             match previous_state.1 {
                 CastleState::Able(CastleSide::Long) => { },
                 CastleState::Able(_) => { black_can_castle_short = true; },
@@ -171,16 +186,16 @@ fn determine_castling_state(previous_state: (CastleState, CastleState), current:
         }
     }
     // White check
-    if let Some(PieceType::King) = current[60].extract_value() {
-        if let Some(PieceType::Rook) = current[56].extract_value() {
+    if let Piece::Piece(PlayerPiece { piece: PieceType::King, player: Player::White }) = current[60] {
+        if let Piece::Piece(PlayerPiece { piece: PieceType::Rook, player: Player::White }) = current[56] {
             match previous_state.0 {
                 CastleState::Able(CastleSide::Short) => { },
                 CastleState::Able(_) => { white_can_castle_long = true; },
                 _ => { },
             }
         }
-        if let Some(PieceType::Rook) = current[63].extract_value() {
-            match previous_state.1 {
+        if let Piece::Piece(PlayerPiece { piece: PieceType::Rook, player: Player::White }) = current[63] {
+            match previous_state.0 {
                 CastleState::Able(CastleSide::Long) => { },
                 CastleState::Able(_) => { white_can_castle_short = true; },
                 _ => { },
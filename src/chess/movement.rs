@@ -1,4 +1,4 @@
-use super::{utils::{Move, Piece, PieceType, PlayerPiece, Player, Castle}, board::{BOARD_LEN, ROW_LEN, Board}};
+use super::{utils::{Move, Piece, PieceType, PlayerPiece, Player, Castle}, board::{BOARD_LEN, ROW_LEN, Board}, checker::{Snapshot, is_in_check}};
 use anyhow::{Result, anyhow};
 
 impl Move {
@@ -48,60 +48,152 @@ impl Move {
 }
 
 pub fn castle(board: &mut Board, options: Castle, turn: Player) -> Result<()> {
-    if board.get_keeper().can_castle(options, turn) {
-        match turn {
-            Player::Black => {
-                match options {
-                    Castle::Long => {
-                        let space1 = matches!(board.get_space(1), Some(Piece::None));
-                        let space2 = matches!(board.get_space(2), Some(Piece::None));
-                        let space3 = matches!(board.get_space(3), Some(Piece::None));
-                        if space1 && space2 && space3 {
-                            board.play_no_rules(Move::new(0, 3)).unwrap();
-                            board.play_no_rules(Move::new(4, 2)).unwrap();
-                            return Ok(());
-                        }
-                    },
-                    Castle::Short => {
-                        let space1 = matches!(board.get_space(5), Some(Piece::None));
-                        let space2 = matches!(board.get_space(6), Some(Piece::None));
-                        if space1 && space2 {
-                            board.play_no_rules(Move::new(7, 5)).unwrap();
-                            board.play_no_rules(Move::new(4, 6)).unwrap();
-                            return Ok(());
-                        }
-                    },
-                }
-            },
-            Player::White => {
-                match options {
-                    Castle::Long => {
-                        let space1 = matches!(board.get_space(57), Some(Piece::None));
-                        let space2 = matches!(board.get_space(58), Some(Piece::None));
-                        let space3 = matches!(board.get_space(59), Some(Piece::None));
-                        if space1 && space2 && space3 {
-                            board.play_no_rules(Move::new(56, 59)).unwrap();
-                            board.play_no_rules(Move::new(60, 58)).unwrap();
-                            return Ok(());
-                        }
-                    },
-                    Castle::Short => {
-                        let space1 = matches!(board.get_space(61), Some(Piece::None));
-                        let space2 = matches!(board.get_space(62), Some(Piece::None));
-                        if space1 && space2 {
-                            board.play_no_rules(Move::new(63, 61)).unwrap();
-                            board.play_no_rules(Move::new(60, 62)).unwrap();
-                            return Ok(());
-                        }
-                    },
+    check_castle(board, options, turn)?;
+
+    let (rook_from, rook_to) = match (turn, options) {
+        (Player::Black, Castle::Long) => (0, 3),
+        (Player::Black, Castle::Short) => (7, 5),
+        (Player::White, Castle::Long) => (56, 59),
+        (Player::White, Castle::Short) => (63, 61),
+    };
+    let king = castle_move(options, turn);
+
+    board.play_no_rules(Move::new(rook_from, rook_to)).unwrap();
+    board.play_no_rules(Move::new(king.from, king.to)).unwrap();
+
+    Ok(())
+}
+
+/// Checks that castling is allowed without changing the board.
+pub fn check_castle(board: &Board, options: Castle, turn: Player) -> Result<()> {
+    if !board.get_keeper().can_castle(options, turn) {
+        return Err(anyhow!("Castling is currently not valid!"));
+    }
+
+    let between: &[usize] = match (turn, options) {
+        (Player::Black, Castle::Long) => &[1, 2, 3],
+        (Player::Black, Castle::Short) => &[5, 6],
+        (Player::White, Castle::Long) => &[57, 58, 59],
+        (Player::White, Castle::Short) => &[61, 62],
+    };
+    if !between.iter().all(|&i| matches!(board.get_space(i), Some(Piece::None))) {
+        return Err(anyhow!("There are pieces in the way!"));
+    }
+
+    // The king may not castle out of, through, or into check.
+    let king_path: [usize; 3] = match (turn, options) {
+        (Player::Black, Castle::Long) => [4, 3, 2],
+        (Player::Black, Castle::Short) => [4, 5, 6],
+        (Player::White, Castle::Long) => [60, 59, 58],
+        (Player::White, Castle::Short) => [60, 61, 62],
+    };
+    let spaces = board.get_spaces();
+    let attacked = |square: usize| {
+        let snap = if square == king_path[0] { Snapshot::new(board) } else { Snapshot::snap(&spaces, &Move::new(king_path[0], square)) };
+        is_in_check(&snap, turn)
+    };
+    if king_path.iter().any(|&square| attacked(square)) {
+        return Err(anyhow!("You can't castle out of, through, or into check!"));
+    }
+
+    Ok(())
+}
+
+/// True if the move is a pawn capturing en passant.
+pub fn is_en_passant(board: &Board, m: &Move) -> bool {
+    if m.castle.is_some() || m.from >= BOARD_LEN || Some(m.to) != board.get_en_passant() { return false; }
+
+    let spaces = board.get_spaces();
+    if let Piece::Piece(PlayerPiece { piece: PieceType::Pawn, player }) = spaces[m.from] {
+        if std::mem::discriminant(&player) != std::mem::discriminant(&board.get_turn()) { return false; }
+        if file_distance(m.from, m.to) != 1 { return false; }
+        let forward = match player {
+            Player::White => m.to / ROW_LEN + 1 == m.from / ROW_LEN,
+            Player::Black => m.to / ROW_LEN == m.from / ROW_LEN + 1,
+        };
+        return forward && matches!(spaces[m.to], Piece::None);
+    }
+
+    false
+}
+
+/// Returns the board after an en passant capture, with the passed pawn removed.
+pub fn en_passant_spaces(board: &Board, m: &Move) -> [Piece; BOARD_LEN] {
+    let mut spaces = board.get_spaces();
+    spaces[m.to] = spaces[m.from];
+    spaces[m.from] = Piece::None;
+    spaces[((m.from / ROW_LEN) * ROW_LEN) + (m.to % ROW_LEN)] = Piece::None;
+    spaces
+}
+
+/// Returns the castle as the king's move, so it can be recorded and highlighted like any other move.
+pub fn castle_move(options: Castle, turn: Player) -> Move {
+    let (from, to) = match (turn, options) {
+        (Player::Black, Castle::Long) => (4, 2),
+        (Player::Black, Castle::Short) => (4, 6),
+        (Player::White, Castle::Long) => (60, 58),
+        (Player::White, Castle::Short) => (60, 62),
+    };
+    Move { from, to, castle: Some(options), promotion: None }
+}
+
+/// Returns every legal move for the side to move. Pawn moves onto the last row
+/// are returned once for each piece the pawn can promote to.
+pub fn legal_moves(board: &Board) -> Vec<Move> {
+    let spaces = board.get_spaces();
+    let turn = board.get_turn();
+    let mut moves = Vec::<Move>::new();
+
+    for from in 0..BOARD_LEN {
+        match spaces[from].extract_player() {
+            Some(player) if std::mem::discriminant(&player) == std::mem::discriminant(&turn) => { },
+            _ => { continue; },
+        }
+
+        for to in 0..BOARD_LEN {
+            let m = Move::new(from, to);
+            if m.is_valid_move(&spaces, turn).is_err() { continue; }
+            if is_in_check(&Snapshot::snap(&spaces, &m), turn) { continue; }
+
+            if m.is_promotion(&spaces) {
+                for piece in PROMOTIONS {
+                    moves.push(Move::promote(from, to, piece));
                 }
-            },
+            } else {
+                moves.push(m);
+            }
         }
+    }
 
-        return Err(anyhow!("There are pieces in the way!"));
+    if let Some(target) = board.get_en_passant() {
+        for from in 0..BOARD_LEN {
+            let m = Move::new(from, target);
+            if is_en_passant(board, &m) && !is_in_check(&Snapshot::from_spaces(en_passant_spaces(board, &m)), turn) {
+                moves.push(m);
+            }
+        }
+    }
+
+    for options in [Castle::Short, Castle::Long] {
+        if check_castle(board, options, turn).is_ok() {
+            moves.push(castle_move(options, turn));
+        }
     }
 
-    Err(anyhow!("Castling is currently not valid!"))
+    moves
+}
+
+pub const PROMOTIONS: [PieceType; 4] = [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight];
+
+impl Move {
+    /// True if this moves a pawn onto the last row.
+    pub fn is_promotion(&self, board: &[Piece; BOARD_LEN]) -> bool {
+        if self.castle.is_some() || self.from >= BOARD_LEN || self.to >= BOARD_LEN { return false; }
+        if let Some(PieceType::Pawn) = board[self.from].extract_value() {
+            return self.to < ROW_LEN || self.to >= BOARD_LEN - ROW_LEN;
+        }
+        false
+    }
 }
 
 fn pawn_movement(board: &[Piece], m: &Move, p: PlayerPiece) -> Result<()> {
@@ -111,14 +203,18 @@ fn pawn_movement(board: &[Piece], m: &Move, p: PlayerPiece) -> Result<()> {
             if m.from < ROW_LEN { return Err(anyhow!("This message should never be read, if the pawn is on top row should be promoted")); }
 
             if let Piece::Piece(_) = board[m.to] {
-                if m.to == m.from - ROW_LEN + 1 || m.to == m.from - ROW_LEN - 1 {
+                if (m.from == m.to + ROW_LEN - 1 || m.from == m.to + ROW_LEN + 1) && file_distance(m.from, m.to) == 1 {
                     return Ok(());
                 }
                 return Err(anyhow!("Pawn can only capture diagonally one space ahead!"));
             }
 
             if m.from >= 48 && m.from < 56 {
-                if m.from - m.to == ROW_LEN || m.from - m.to == ROW_LEN * 2 {
+                if m.from - m.to == ROW_LEN {
+                    return Ok(());
+                }
+                if m.from - m.to == ROW_LEN * 2 {
+                    if let Piece::Piece(_) = board[m.from - ROW_LEN] { return Err(anyhow!("There is a piece in the way!")); }
                     return Ok(());
                 }
                 return Err(anyhow!("Pawn can only move straight forward!"));
@@ -133,14 +229,18 @@ fn pawn_movement(board: &[Piece], m: &Move, p: PlayerPiece) -> Result<()> {
             if m.to < m.from { return Err(anyhow!("Pawn cannot move backwards!")); }
 
             if let Piece::Piece(_) = board[m.to] {
-                if m.to == m.from + ROW_LEN + 1 || m.to == m.from + ROW_LEN - 1 {
+                if (m.to == m.from + ROW_LEN + 1 || m.to == m.from + ROW_LEN - 1) && file_distance(m.from, m.to) == 1 {
                     return Ok(());
                 }
                 return Err(anyhow!("Pawn can only capture diagonally one space ahead!"));
             }
 
             if m.from >= 8 && m.from < 16 {
-                if m.to - m.from == ROW_LEN || m.to - m.from == ROW_LEN * 2 {
+                if m.to - m.from == ROW_LEN {
+                    return Ok(());
+                }
+                if m.to - m.from == ROW_LEN * 2 {
+                    if let Piece::Piece(_) = board[m.from + ROW_LEN] { return Err(anyhow!("There is a piece in the way!")); }
                     return Ok(());
                 }
                 return Err(anyhow!("Pawn can only move straight forward!"));
@@ -155,10 +255,11 @@ fn pawn_movement(board: &[Piece], m: &Move, p: PlayerPiece) -> Result<()> {
 }
 
 fn king_movement(m: &Move) -> Result<()> {
+    if file_distance(m.from, m.to) > 1 { return Err(anyhow!("The King can only move horizontally, vertically, and diagonally one space!")); }
     if m.to < m.from { // Moving up or left
         if m.to == m.from - 1 { return Ok(()); } // move just left
         if m.from < ROW_LEN { return Err(anyhow!("The King can only move horizontally, vertically, and diagonally one space!")); } // cant move up on top row
-        if m.to == m.from - ROW_LEN || m.to == m.from - ROW_LEN - 1 || m.to == m.from - ROW_LEN + 1 { return Ok(()); } // moving up
+        if m.from == m.to + ROW_LEN || m.from == m.to + ROW_LEN + 1 || m.from == m.to + ROW_LEN - 1 { return Ok(()); } // moving up
     } else { // Moving down or right
         if m.to == m.from + 1 { return Ok(()); } // move just right
         if m.from >= BOARD_LEN - ROW_LEN { return Err(anyhow!("The King can only move horizontally, vertically, and diagonally one space!")); } // cant move down on bottom row
@@ -332,13 +433,23 @@ fn knight_movement(m: &Move) -> Result<()> {
     let from = m.from as i32;
     let row_len = ROW_LEN as i32;
 
+    if file_distance(m.from, m.to) == 0 || file_distance(m.from, m.to) > 2 {
+        return Err(anyhow!("Knights can only move in those weird 'L' shaped patterns!"));
+    }
+
     if to == from - row_len - 2 || to == from - row_len + 2 || to == from + row_len - 2 || to == from + row_len + 2 ||
        to == from - (row_len * 2) - 1 || to == from - (row_len * 2) + 1 || to == from + (row_len * 2) - 1 || to == from + (row_len * 2) + 1 { return Ok(()); }
 
     Err(anyhow!("Knights can only move in those weird 'L' shaped patterns!"))
 }
 
-enum Direction {
+/// Number of columns between two indices, so moves can't wrap around the edge of the board.
+fn file_distance(a: usize, b: usize) -> usize {
+    (a % ROW_LEN).abs_diff(b % ROW_LEN)
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
     North,
     Northeast,
     East,
@@ -349,7 +460,12 @@ enum Direction {
     Northwest,
 }
 
-fn index_in_raycast(from: usize, direction: Direction, needle: usize) -> bool {
+impl Direction {
+    pub(crate) const ORTHOGONAL: [Direction; 4] = [Direction::North, Direction::East, Direction::South, Direction::West];
+    pub(crate) const DIAGONAL: [Direction; 4] = [Direction::Northeast, Direction::Southeast, Direction::Southwest, Direction::Northwest];
+}
+
+pub(crate) fn index_in_raycast(from: usize, direction: Direction, needle: usize) -> bool {
     if from >= BOARD_LEN { return false; }
 
     let mut focus = from;
@@ -393,7 +509,7 @@ fn index_in_raycast(from: usize, direction: Direction, needle: usize) -> bool {
             }
         },
         Direction::Southwest => {
-            while focus < BOARD_LEN - ROW_LEN && (focus % ROW_LEN) > 0 {
+            while focus < BOARD_LEN - ROW_LEN && !focus.is_multiple_of(ROW_LEN) {
                 focus += ROW_LEN;
                 focus -= 1;
 
@@ -401,14 +517,14 @@ fn index_in_raycast(from: usize, direction: Direction, needle: usize) -> bool {
             }
         },
         Direction::West => {
-            while (focus % ROW_LEN) > 0 {
+            while !focus.is_multiple_of(ROW_LEN) {
                 focus -= 1;
 
                 if focus == needle { return true; }
             }
         },
         Direction::Northwest => {
-            while focus >= ROW_LEN && (focus % ROW_LEN) > 0 {
+            while focus >= ROW_LEN && !focus.is_multiple_of(ROW_LEN) {
                 focus -= ROW_LEN;
                 focus -= 1;
 
@@ -420,7 +536,8 @@ fn index_in_raycast(from: usize, direction: Direction, needle: usize) -> bool {
     false
 }
 
-fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize> {
+/// The first occupied square from `from` (not included) in the direction, if any.
+pub(crate) fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize> {
     if from >= BOARD_LEN { return None; }
 
     let mut focus = from;
@@ -474,7 +591,7 @@ fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize>
             }
         },
         Direction::Southwest => {
-            while focus < BOARD_LEN - ROW_LEN && (focus % ROW_LEN) > 0 {
+            while focus < BOARD_LEN - ROW_LEN && !focus.is_multiple_of(ROW_LEN) {
                 focus += ROW_LEN;
                 focus -= 1;
 
@@ -484,7 +601,7 @@ fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize>
             }
         },
         Direction::West => {
-            while (focus % ROW_LEN) > 0 {
+            while !focus.is_multiple_of(ROW_LEN) {
                 focus -= 1;
 
                 if let Piece::Piece(_) = board[focus] {
@@ -493,7 +610,7 @@ fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize>
             }
         },
         Direction::Northwest => {
-            while focus >= ROW_LEN && (focus % ROW_LEN) > 0 {
+            while focus >= ROW_LEN && !focus.is_multiple_of(ROW_LEN) {
                 focus -= ROW_LEN;
                 focus -= 1;
 
@@ -507,3 +624,20 @@ fn raycast(board: &[Piece], from: usize, direction: Direction) -> Option<usize>
     None
 }
 
+
+/// Counts the leaf nodes of the legal move tree to the given depth, for checking the move generator.
+pub fn perft(board: &Board, depth: u32) -> u64 {
+    let moves = legal_moves(board);
+    if depth <= 1 {
+        return if depth == 0 { 1 } else { moves.len() as u64 };
+    }
+
+    let mut nodes = 0;
+    for m in moves {
+        let mut child = board.clone();
+        if child.play(m).is_err() { continue; }
+        child.next_turn();
+        nodes += perft(&child, depth - 1);
+    }
+    nodes
+}
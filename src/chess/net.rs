@@ -0,0 +1,584 @@
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{channel, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use super::{
+    board::Board,
+    broadcast::Broadcaster,
+    checker::{self, outcome, Snapshot},
+    interpret::{interpret_notation, parse_uci},
+    render::{Orientation, RenderOptions},
+    san::{to_san, to_uci},
+    utils::{Move, PieceType, Player},
+};
+
+// Line protocol between the host and the player who joins. Every message is one line.
+//
+// Joiner to host:
+//   HELLO <name>            sent after connecting
+//   MOVE <uci>              play a move, e.g. MOVE e2e4
+//   RESIGN
+//   DRAW OFFER | DRAW ACCEPT | DRAW DECLINE
+//   BYE                     leaving for good
+//
+// Host to joiner:
+//   WELCOME <white|black> <host name>
+//   SYNC <start fen>|<uci moves>   the whole game, sent on every (re)connect
+//   MOVED <uci>             a move was played, by either side
+//   ERROR <message>         the last request was refused
+//   DRAW OFFERED | DRAW DECLINED
+//   RESULT <1-0|0-1|1/2-1/2> <reason>
+//   BYE
+
+pub const DEFAULT_PORT: u16 = 7878;
+
+const RECONNECT_ATTEMPTS: u32 = 30;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// Events from the network carry the id of the connection they came from, so a reader that
+/// outlives its connection can't act on the one that replaced it.
+enum Event {
+    Local(String),
+    Remote(u64, String),
+    Connected(u64, TcpStream),
+    Disconnected(u64),
+}
+
+/// State shared by both ends: a copy of the game and what to show under the board.
+struct Session {
+    board: Board,
+    me: Player,
+    my_name: String,
+    their_name: String,
+    connected: bool,
+    draw_offer: Option<Player>,
+    result: Option<String>,
+    status: String,
+    /// Time each side has spent thinking, kept by the host for spectators.
+    white_clock: Duration,
+    black_clock: Duration,
+    /// When the side to move started thinking.
+    turn_started: Instant,
+}
+
+impl Session {
+    fn new(me: Player, my_name: &str) -> Self {
+        Self {
+            board: Board::new(),
+            me,
+            my_name: my_name.to_string(),
+            their_name: "Opponent".to_string(),
+            connected: false,
+            draw_offer: None,
+            result: None,
+            status: String::new(),
+            white_clock: Duration::ZERO,
+            black_clock: Duration::ZERO,
+            turn_started: Instant::now(),
+        }
+    }
+
+    /// Charges the time since the turn started to the side to move and starts the next turn.
+    fn end_turn(&mut self) {
+        let spent = self.turn_started.elapsed();
+        match self.board.get_turn() {
+            Player::White => self.white_clock += spent,
+            Player::Black => self.black_clock += spent,
+        }
+        self.turn_started = Instant::now();
+    }
+
+    fn them(&self) -> Player {
+        self.me.opponent()
+    }
+
+    fn my_turn(&self) -> bool {
+        std::mem::discriminant(&self.board.get_turn()) == std::mem::discriminant(&self.me)
+    }
+
+    fn name_of(&self, player: Player) -> &str {
+        if std::mem::discriminant(&player) == std::mem::discriminant(&self.me) { &self.my_name } else { &self.their_name }
+    }
+
+    fn render(&self) {
+        clearscr!();
+        let options = RenderOptions {
+            orientation: match self.me {
+                Player::White => Orientation::White,
+                Player::Black => Orientation::Black,
+            },
+            ..RenderOptions::default()
+        };
+        self.board.print_with(&options);
+
+        println!("{} vs {}{}", self.name_of(Player::White).bold(), self.name_of(Player::Black).green().bold(),
+            if self.connected { "" } else { "   (opponent not connected)" });
+        if !self.status.is_empty() {
+            println!("{}", self.status);
+        }
+
+        match &self.result {
+            Some(result) => println!("Game over: {}. Type q to leave.", result),
+            None => {
+                if checker::is_in_check(&Snapshot::new(&self.board), self.board.get_turn()) {
+                    println!("C H E C K !");
+                }
+                if self.my_turn() {
+                    println!("Your move! (or resign, draw, accept, decline, q)");
+                } else {
+                    println!("Waiting for {}... (resign, draw, q)", self.their_name);
+                }
+            },
+        }
+    }
+
+    /// Sets the result if the side to move is checkmated or stalemated.
+    fn check_outcome(&mut self) -> Option<String> {
+        if self.result.is_some() {
+            return None;
+        }
+        let line = match outcome(&self.board)? {
+            checker::Outcome::Checkmate(winner) => format!("{} checkmate", checker::Outcome::Checkmate(winner).result()),
+            checker::Outcome::Stalemate => "1/2-1/2 stalemate".to_string(),
+        };
+        self.result = Some(line.clone());
+        Some(line)
+    }
+
+    /// Reads a move typed at this terminal. Promotions default to a queen.
+    fn read_move(&self, input: &str) -> Result<Move> {
+        let mut m = interpret_notation(input, &self.board)?;
+        if m.is_promotion(&self.board.get_spaces()) && m.promotion.is_none() {
+            m.promotion = Some(PieceType::Queen);
+        }
+        Ok(m)
+    }
+}
+
+fn side_name(player: Player) -> &'static str {
+    match player {
+        Player::White => "white",
+        Player::Black => "black",
+    }
+}
+
+fn send(peer: &mut Option<TcpStream>, line: &str) {
+    if let Some(stream) = peer {
+        if writeln!(stream, "{}", line).is_err() {
+            *peer = None;
+        }
+    }
+}
+
+fn spawn_stdin(events: Sender<Event>) {
+    thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            match line {
+                Ok(line) => { if events.send(Event::Local(line)).is_err() { break; } },
+                Err(_) => { break; },
+            }
+        }
+    });
+}
+
+/// Forwards each line from connection `id` as an event, then reports the disconnect.
+fn read_lines(stream: TcpStream, id: u64, events: &Sender<Event>) {
+    for line in BufReader::new(stream).lines() {
+        match line {
+            Ok(line) => { if events.send(Event::Remote(id, line)).is_err() { return; } },
+            Err(_) => { break; },
+        }
+    }
+    let _ = events.send(Event::Disconnected(id));
+}
+
+/// Runs the authoritative side of a LAN game: waits for a player to join on the port and plays `side`.
+/// With a broadcaster, spectators can follow the game too.
+pub fn host(port: u16, side: Player, name: &str, broadcast: Option<Broadcaster>) -> Result<()> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).with_context(|| format!("Could not listen on port {}", port))?;
+    let (events, inbox) = channel::<Event>();
+
+    let accept_events = events.clone();
+    thread::spawn(move || {
+        for (id, stream) in (0..).zip(listener.incoming().flatten()) {
+            if accept_events.send(Event::Connected(id, stream)).is_err() { break; }
+        }
+    });
+    spawn_stdin(events.clone());
+
+    let mut session = Session::new(side, name);
+    session.status = format!("Waiting for a player to join on port {}...", port);
+    let mut peer: Option<TcpStream> = None;
+    let mut peer_id: Option<u64> = None;
+
+    session.render();
+    while let Ok(event) = inbox.recv() {
+        match event {
+            Event::Connected(id, stream) => {
+                if peer.is_some() {
+                    let mut extra = Some(stream);
+                    send(&mut extra, "ERROR This game already has two players");
+                    continue;
+                }
+                let reader = stream.try_clone()?;
+                let reader_events = events.clone();
+                thread::spawn(move || read_lines(reader, id, &reader_events));
+
+                peer = Some(stream);
+                peer_id = Some(id);
+                session.connected = true;
+                // Nobody thinks while waiting for an opponent.
+                session.turn_started = Instant::now();
+                session.status = "Opponent connected.".to_string();
+                send(&mut peer, &format!("WELCOME {} {}", side_name(session.them()), session.my_name));
+                send(&mut peer, &sync_line(&session.board));
+                if let Some(result) = &session.result {
+                    send(&mut peer, &format!("RESULT {}", result));
+                }
+            },
+            Event::Disconnected(id) | Event::Remote(id, _) if peer_id != Some(id) => { continue; },
+            Event::Disconnected(_) => {
+                peer = None;
+                peer_id = None;
+                session.connected = false;
+                session.status = "Opponent disconnected. Waiting for them to reconnect...".to_string();
+            },
+            Event::Remote(_, line) => {
+                host_remote(&mut session, &mut peer, line.trim());
+            },
+            Event::Local(line) => {
+                let input = line.trim();
+                if input.eq_ignore_ascii_case("q") {
+                    send(&mut peer, "BYE");
+                    return Ok(());
+                }
+                host_local(&mut session, &mut peer, input);
+            },
+        }
+        if let Some(broadcast) = &broadcast {
+            broadcast.set_players(session.name_of(Player::White), session.name_of(Player::Black));
+            broadcast.update(&session.board, Some((session.white_clock, session.black_clock)));
+            if let Some(result) = &session.result {
+                broadcast.finish(result);
+            }
+        }
+        session.render();
+    }
+
+    Ok(())
+}
+
+fn sync_line(board: &Board) -> String {
+    let moves: Vec<_> = board.get_history().iter().map(to_uci).collect();
+    format!("SYNC {}|{}", board.get_start_fen(), moves.join(" "))
+}
+
+/// Plays a move on the host's board for either side and tells the joiner.
+fn host_play(session: &mut Session, peer: &mut Option<TcpStream>, m: Move) -> Result<()> {
+    let san = to_san(&session.board, &m);
+    session.board.play(m)?;
+    session.end_turn();
+    session.board.next_turn();
+    session.draw_offer = None;
+    send(peer, &format!("MOVED {}", to_uci(&m)));
+
+    session.status = format!("Last move: {}", san);
+    if let Some(result) = session.check_outcome() {
+        send(peer, &format!("RESULT {}", result));
+    }
+    Ok(())
+}
+
+fn host_remote(session: &mut Session, peer: &mut Option<TcpStream>, line: &str) {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let them = session.them();
+
+    match command {
+        "HELLO" => {
+            if !rest.trim().is_empty() {
+                session.their_name = rest.trim().to_string();
+            }
+        },
+        "MOVE" => {
+            if session.result.is_some() {
+                send(peer, "ERROR The game is over");
+            } else if session.my_turn() {
+                send(peer, "ERROR It is not your turn");
+            } else {
+                let played = parse_uci(rest.trim(), &session.board).and_then(|m| host_play(session, peer, m));
+                if let Err(error) = played {
+                    send(peer, &format!("ERROR {}", error));
+                }
+            }
+        },
+        "RESIGN" if session.result.is_none() => {
+            let result = format!("{} {} resigned", win_for(session.me), session.their_name);
+            send(peer, &format!("RESULT {}", result));
+            session.result = Some(result);
+        },
+        "DRAW" if session.result.is_none() => {
+            match rest.trim() {
+                "OFFER" => {
+                    session.draw_offer = Some(them);
+                    session.status = format!("{} offers a draw. Type accept or decline.", session.their_name);
+                },
+                "ACCEPT" if matches!(session.draw_offer, Some(p) if std::mem::discriminant(&p) == std::mem::discriminant(&session.me)) => {
+                    let result = "1/2-1/2 draw agreed".to_string();
+                    send(peer, &format!("RESULT {}", result));
+                    session.result = Some(result);
+                },
+                "DECLINE" => {
+                    session.draw_offer = None;
+                    session.status = format!("{} declined the draw.", session.their_name);
+                },
+                _ => send(peer, "ERROR There is no draw offer to answer"),
+            }
+        },
+        "RESIGN" | "DRAW" => send(peer, "ERROR The game is over"),
+        "BYE" => {
+            session.status = format!("{} left the game.", session.their_name);
+        },
+        _ => send(peer, &format!("ERROR Unknown message '{}'", command)),
+    }
+}
+
+fn host_local(session: &mut Session, peer: &mut Option<TcpStream>, input: &str) {
+    if session.result.is_some() {
+        return;
+    }
+
+    match input.to_lowercase().as_str() {
+        "resign" => {
+            let result = format!("{} {} resigned", win_for(session.them()), session.my_name);
+            send(peer, &format!("RESULT {}", result));
+            session.result = Some(result);
+        },
+        "draw" => {
+            session.draw_offer = Some(session.me);
+            send(peer, "DRAW OFFERED");
+            session.status = "Draw offered.".to_string();
+        },
+        "accept" => {
+            if matches!(session.draw_offer, Some(p) if std::mem::discriminant(&p) == std::mem::discriminant(&session.them())) {
+                let result = "1/2-1/2 draw agreed".to_string();
+                send(peer, &format!("RESULT {}", result));
+                session.result = Some(result);
+            } else {
+                session.status = "There is no draw offer to accept.".to_string();
+            }
+        },
+        "decline" => {
+            session.draw_offer = None;
+            send(peer, "DRAW DECLINED");
+            session.status = "Draw declined.".to_string();
+        },
+        _ => {
+            if !session.my_turn() {
+                session.status = "It is not your turn!".to_string();
+                return;
+            }
+            let played = session.read_move(input).and_then(|m| host_play(session, peer, m));
+            if let Err(error) = played {
+                session.status = error.to_string();
+            }
+        },
+    }
+}
+
+/// The result string for a win by `player`.
+fn win_for(player: Player) -> &'static str {
+    match player {
+        Player::White => "1-0",
+        Player::Black => "0-1",
+    }
+}
+
+/// Joins a game hosted at `address` (`host` or `host:port`) and mirrors its board.
+pub fn join(address: &str, name: &str) -> Result<()> {
+    let address = if address.contains(':') { address.to_string() } else { format!("{}:{}", address, DEFAULT_PORT) };
+    let stream = TcpStream::connect(&address).with_context(|| format!("Could not connect to {}", address))?;
+
+    let (events, inbox) = channel::<Event>();
+    spawn_stdin(events.clone());
+
+    let reader_events = events.clone();
+    let reader_address = address.clone();
+    let reader = stream.try_clone()?;
+    thread::spawn(move || {
+        let mut stream = reader;
+        for id in 0.. {
+            read_lines(stream, id, &reader_events);
+            match reconnect(&reader_address) {
+                Some(new_stream) => {
+                    stream = match new_stream.try_clone() {
+                        Ok(s) => s,
+                        Err(_) => { return; },
+                    };
+                    if reader_events.send(Event::Connected(id + 1, new_stream)).is_err() { return; }
+                },
+                None => { return; },
+            }
+        }
+    });
+
+    let mut session = Session::new(Player::Black, name);
+    session.connected = true;
+    let mut peer = Some(stream);
+    let mut peer_id = 0;
+    send(&mut peer, &format!("HELLO {}", name));
+
+    session.render();
+    while let Ok(event) = inbox.recv() {
+        match event {
+            Event::Connected(id, stream) => {
+                peer = Some(stream);
+                peer_id = id;
+                session.connected = true;
+                session.status = "Reconnected.".to_string();
+                send(&mut peer, &format!("HELLO {}", session.my_name));
+            },
+            Event::Disconnected(id) | Event::Remote(id, _) if id != peer_id => { continue; },
+            Event::Disconnected(_) => {
+                peer = None;
+                session.connected = false;
+                session.status = "Lost the connection to the host. Reconnecting...".to_string();
+            },
+            Event::Remote(_, line) => {
+                if let Err(error) = join_remote(&mut session, line.trim()) {
+                    session.status = format!("Bad message from the host: {}", error);
+                }
+                if line.trim() == "BYE" {
+                    session.render();
+                    return Ok(());
+                }
+            },
+            Event::Local(line) => {
+                let input = line.trim();
+                if input.eq_ignore_ascii_case("q") {
+                    send(&mut peer, "BYE");
+                    return Ok(());
+                }
+                join_local(&mut session, &mut peer, input);
+            },
+        }
+        session.render();
+    }
+
+    Ok(())
+}
+
+fn reconnect(address: &str) -> Option<TcpStream> {
+    for _ in 0..RECONNECT_ATTEMPTS {
+        thread::sleep(RECONNECT_DELAY);
+        if let Ok(stream) = TcpStream::connect(address) {
+            return Some(stream);
+        }
+    }
+    None
+}
+
+fn join_remote(session: &mut Session, line: &str) -> Result<()> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+
+    match command {
+        "WELCOME" => {
+            let (side, host_name) = rest.split_once(' ').unwrap_or((rest, "Host"));
+            session.me = match side {
+                "white" => Player::White,
+                "black" => Player::Black,
+                _ => { return Err(anyhow!("unknown side '{}'", side)); },
+            };
+            session.their_name = host_name.to_string();
+        },
+        "SYNC" => {
+            let (fen, moves) = rest.split_once('|').ok_or_else(|| anyhow!("SYNC needs a position and moves"))?;
+            let mut board = Board::from_fen(fen)?;
+            for text in moves.split_whitespace() {
+                let m = parse_uci(text, &board)?;
+                board.play(m)?;
+                board.next_turn();
+            }
+            session.board = board;
+            session.result = None;
+            session.check_outcome();
+        },
+        "MOVED" => {
+            let m = parse_uci(rest.trim(), &session.board)?;
+            let san = to_san(&session.board, &m);
+            session.board.play(m)?;
+            session.board.next_turn();
+            session.draw_offer = None;
+            session.status = format!("Last move: {}", san);
+            session.check_outcome();
+        },
+        "ERROR" => { session.status = rest.to_string(); },
+        "DRAW" => {
+            match rest.trim() {
+                "OFFERED" => {
+                    session.draw_offer = Some(session.them());
+                    session.status = format!("{} offers a draw. Type accept or decline.", session.their_name);
+                },
+                "DECLINED" => {
+                    session.draw_offer = None;
+                    session.status = format!("{} declined the draw.", session.their_name);
+                },
+                _ => { },
+            }
+        },
+        "RESULT" => { session.result = Some(rest.to_string()); },
+        "BYE" => { session.status = format!("{} closed the game.", session.their_name); },
+        _ => { },
+    }
+
+    Ok(())
+}
+
+fn join_local(session: &mut Session, peer: &mut Option<TcpStream>, input: &str) {
+    if session.result.is_some() {
+        return;
+    }
+    if peer.is_none() {
+        session.status = "Not connected to the host yet.".to_string();
+        return;
+    }
+
+    match input.to_lowercase().as_str() {
+        "resign" => send(peer, "RESIGN"),
+        "draw" => {
+            send(peer, "DRAW OFFER");
+            session.draw_offer = Some(session.me);
+            session.status = "Draw offered.".to_string();
+        },
+        "accept" => send(peer, "DRAW ACCEPT"),
+        "decline" => {
+            send(peer, "DRAW DECLINE");
+            session.draw_offer = None;
+            session.status = "Draw declined.".to_string();
+        },
+        _ => {
+            if !session.my_turn() {
+                session.status = "It is not your turn!".to_string();
+                return;
+            }
+            // Checked here for a quick answer; the host checks again before playing it.
+            match session.read_move(input) {
+                Ok(m) => {
+                    let mut check = session.board.clone();
+                    match check.play(m) {
+                        Ok(()) => {
+                            send(peer, &format!("MOVE {}", to_uci(&m)));
+                            session.status = "Move sent.".to_string();
+                        },
+                        Err(error) => { session.status = error.to_string(); },
+                    }
+                },
+                Err(error) => { session.status = error.to_string(); },
+            }
+        },
+    }
+}
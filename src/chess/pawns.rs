@@ -0,0 +1,177 @@
+use super::{
+    board::{Board, ROW_LEN},
+    utils::{Piece, PieceType, Player},
+};
+
+// Ranks here count from the pawn owner's side, 0 being its first rank, so "ahead" means a higher
+// rank for either color. Squares are board indices.
+
+const DOUBLED: i32 = -15;
+const ISOLATED: i32 = -15;
+const BACKWARD: i32 = -10;
+const CONNECTED: i32 = 5;
+const CANDIDATE: i32 = 10;
+/// For every island past the first.
+const ISLAND: i32 = -5;
+/// Bonus for a passed pawn by its rank.
+const PASSED: [i32; ROW_LEN] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+/// One side's pawns, sorted into the usual strengths and weaknesses. A pawn can be in several lists.
+#[derive(Default)]
+pub struct SidePawns {
+    pub pawns: Vec<usize>,
+    /// Pawns with another pawn of their side ahead of them on the file.
+    pub doubled: Vec<usize>,
+    /// Pawns with no pawns of their side on the neighbouring files.
+    pub isolated: Vec<usize>,
+    /// Pawns left behind by their neighbours that can't step up without being taken.
+    pub backward: Vec<usize>,
+    /// Pawns no enemy pawn can stop on their way to promotion.
+    pub passed: Vec<usize>,
+    /// Pawns with a neighbour of their side beside them or a rank away.
+    pub connected: Vec<usize>,
+    /// Pawns on a file free of enemy pawns with at least as many supporters as enemy pawns in the
+    /// way, so they can become passed.
+    pub candidates: Vec<usize>,
+    /// Groups of pawns on neighbouring files, as the first and last file of each.
+    pub islands: Vec<(usize, usize)>,
+    /// Files where the side has no pawns but the opponent has.
+    pub half_open_files: Vec<usize>,
+}
+
+pub struct PawnStructure {
+    pub white: SidePawns,
+    pub black: SidePawns,
+    /// Files without pawns of either color.
+    pub open_files: Vec<usize>,
+}
+
+impl PawnStructure {
+    pub fn side(&self, player: Player) -> &SidePawns {
+        match player {
+            Player::White => &self.white,
+            Player::Black => &self.black,
+        }
+    }
+
+    /// The structure's worth in centipawns from White's point of view.
+    pub fn score(&self) -> i32 {
+        side_score(&self.white, Player::White) - side_score(&self.black, Player::Black)
+    }
+}
+
+/// Finds every pawn of both sides and sorts them.
+pub fn pawn_structure(board: &Board) -> PawnStructure {
+    // Each side's pawns on each file, as a bit per rank counted from that side.
+    let mut files = [[0u8; ROW_LEN]; 2];
+    for (square, piece) in board.get_spaces().iter().enumerate() {
+        let Piece::Piece(p) = piece else { continue; };
+        if !matches!(p.piece, PieceType::Pawn) { continue; }
+        files[p.player.index()][square % ROW_LEN] |= 1 << rank(square, p.player);
+    }
+
+    let open_files = (0..ROW_LEN).filter(|&f| files[0][f] == 0 && files[1][f] == 0).collect();
+    PawnStructure {
+        white: side_pawns(&files, Player::White),
+        black: side_pawns(&files, Player::Black),
+        open_files,
+    }
+}
+
+fn side_pawns(files: &[[u8; ROW_LEN]; 2], player: Player) -> SidePawns {
+    let (mine, theirs) = match player {
+        Player::White => (&files[0], &files[1]),
+        Player::Black => (&files[1], &files[0]),
+    };
+    // The enemy's pawns with their ranks counted from our side.
+    let enemy = theirs.map(u8::reverse_bits);
+    let neighbours = |masks: &[u8; ROW_LEN], file: usize| {
+        (if file > 0 { masks[file - 1] } else { 0 }) | (if file + 1 < ROW_LEN { masks[file + 1] } else { 0 })
+    };
+
+    let mut side = SidePawns::default();
+    for file in 0..ROW_LEN {
+        let adjacent = neighbours(mine, file);
+        let adjacent_enemy = neighbours(&enemy, file);
+        for r in (0..ROW_LEN).filter(|&r| mine[file] & (1 << r) != 0) {
+            let square = square_at(file, r, player);
+            side.pawns.push(square);
+
+            let ahead = ranks_above(r);
+            let friend_ahead = mine[file] & ahead != 0;
+            let enemy_ahead = enemy[file] & ahead != 0;
+            let sentries = (adjacent_enemy & ahead).count_ones();
+            let passed = !friend_ahead && !enemy_ahead && sentries == 0;
+
+            if friend_ahead {
+                side.doubled.push(square);
+            }
+            if adjacent == 0 {
+                side.isolated.push(square);
+            }
+            if adjacent & ((0b111u16 << r >> 1) as u8) != 0 {
+                side.connected.push(square);
+            }
+            if passed {
+                side.passed.push(square);
+            }
+            // Backward: every neighbour is further up, and an enemy pawn guards the square ahead.
+            let stop_guarded = r + 2 < ROW_LEN && adjacent_enemy & (1 << (r + 2)) != 0;
+            if adjacent != 0 && adjacent & !ahead == 0 && stop_guarded && !passed {
+                side.backward.push(square);
+            }
+            let supporters = (adjacent & !ahead).count_ones();
+            if !passed && !friend_ahead && !enemy_ahead && supporters >= sentries {
+                side.candidates.push(square);
+            }
+        }
+    }
+
+    for file in 0..ROW_LEN {
+        if mine[file] == 0 {
+            if theirs[file] != 0 {
+                side.half_open_files.push(file);
+            }
+        } else if file > 0 && mine[file - 1] != 0 {
+            if let Some(island) = side.islands.last_mut() {
+                island.1 = file;
+            }
+        } else {
+            side.islands.push((file, file));
+        }
+    }
+
+    side
+}
+
+/// The ranks past `rank`, as bits.
+fn ranks_above(rank: usize) -> u8 {
+    (0xFFu16 << (rank + 1)) as u8
+}
+
+fn side_score(side: &SidePawns, player: Player) -> i32 {
+    let passed: i32 = side.passed.iter().map(|&square| PASSED[rank(square, player)]).sum();
+    side.doubled.len() as i32 * DOUBLED
+        + side.isolated.len() as i32 * ISOLATED
+        + side.backward.len() as i32 * BACKWARD
+        + side.connected.len() as i32 * CONNECTED
+        + side.candidates.len() as i32 * CANDIDATE
+        + side.islands.len().saturating_sub(1) as i32 * ISLAND
+        + passed
+}
+
+/// The square's rank from the player's side, 0 to 7.
+fn rank(square: usize, player: Player) -> usize {
+    match player {
+        Player::White => ROW_LEN - 1 - square / ROW_LEN,
+        Player::Black => square / ROW_LEN,
+    }
+}
+
+fn square_at(file: usize, rank: usize, player: Player) -> usize {
+    let row = match player {
+        Player::White => ROW_LEN - 1 - rank,
+        Player::Black => rank,
+    };
+    row * ROW_LEN + file
+}
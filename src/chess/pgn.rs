@@ -0,0 +1,270 @@
+use std::collections::BTreeMap;
+use anyhow::{anyhow, Context, Result};
+use super::{
+    board::{Board, STARTING_FEN},
+    checker::outcome,
+    eco::classify,
+    san::{parse_san, to_san},
+    utils::Player,
+};
+
+const SEVEN_TAG_ROSTER: [&str; 7] = ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// A game read from or written to Portable Game Notation.
+pub struct PgnGame {
+    pub tags: Vec<(String, String)>,
+    /// The final position. Its history holds the moves of the game.
+    pub board: Board,
+    /// Comments written after a move, keyed by the move's index in the history.
+    pub comments: BTreeMap<usize, String>,
+    /// Numeric annotation glyphs after a move (`$2` is a mistake), keyed the same way.
+    pub nags: BTreeMap<usize, Vec<u8>>,
+}
+
+impl PgnGame {
+    pub fn new(board: Board) -> Self {
+        let mut game = Self { tags: Vec::new(), board, comments: BTreeMap::new(), nags: BTreeMap::new() };
+        for name in SEVEN_TAG_ROSTER {
+            game.set_tag(name, "?");
+        }
+        let result = game.computed_result();
+        game.set_tag("Result", result);
+        game
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(key, _)| key == name) {
+            Some(tag) => { tag.1 = value.to_string(); },
+            None => { self.tags.push((name.to_string(), value.to_string())); },
+        }
+    }
+
+    /// The result of the game: from the `Result` tag if it is set, otherwise from the final position.
+    pub fn result(&self) -> &str {
+        match self.tag("Result") {
+            Some(result) if result != "?" => result,
+            _ => self.computed_result(),
+        }
+    }
+
+    fn computed_result(&self) -> &'static str {
+        match outcome(&self.board) {
+            Some(outcome) => outcome.result(),
+            None => "*",
+        }
+    }
+
+    pub fn to_pgn(&self) -> String {
+        let mut pgn = String::new();
+
+        let start = self.board.get_start_fen();
+        let mut tags = self.tags.clone();
+        if start != STARTING_FEN {
+            tags.retain(|(key, _)| key != "SetUp" && key != "FEN");
+            tags.push(("SetUp".to_string(), "1".to_string()));
+            tags.push(("FEN".to_string(), start.to_string()));
+        }
+        // Name the opening unless the game already has a name for it.
+        if self.tag("ECO").is_none() {
+            if let Some(opening) = classify(&self.board) {
+                tags.push(("ECO".to_string(), opening.code.to_string()));
+                tags.push(("Opening".to_string(), opening.name.to_string()));
+            }
+        }
+        for (key, value) in &tags {
+            pgn.push_str(&format!("[{} \"{}\"]\n", key, value.replace('\\', "\\\\").replace('"', "\\\"")));
+        }
+        pgn.push('\n');
+
+        let mut tokens = Vec::<String>::new();
+        let mut replay = Board::from_fen(start).unwrap_or_default();
+        for (i, m) in self.board.get_history().iter().enumerate() {
+            let white_to_move = matches!(replay.get_turn(), Player::White);
+            if white_to_move {
+                tokens.push(format!("{}.", replay.get_fullmove_number()));
+            } else if i == 0 {
+                tokens.push(format!("{}...", replay.get_fullmove_number()));
+            }
+            tokens.push(to_san(&replay, m));
+            for nag in self.nags.get(&i).into_iter().flatten() {
+                tokens.push(format!("${}", nag));
+            }
+            if let Some(comment) = self.comments.get(&i) {
+                tokens.push(format!("{{{}}}", comment.replace('}', ")")));
+            }
+            if replay.play(*m).is_err() { break; }
+            replay.next_turn();
+        }
+        tokens.push(self.result().to_string());
+
+        pgn.push_str(&wrap(&tokens, 80));
+        pgn.push('\n');
+        pgn
+    }
+}
+
+/// Lines the tokens up into lines no longer than `width`.
+fn wrap(tokens: &[String], width: usize) -> String {
+    let mut text = String::new();
+    let mut line_len = 0;
+    for token in tokens {
+        if line_len > 0 && line_len + 1 + token.len() > width {
+            text.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            text.push(' ');
+            line_len += 1;
+        }
+        text.push_str(token);
+        line_len += token.len();
+    }
+    text
+}
+
+/// Reads every game in a PGN file. Comments and annotation glyphs after moves are kept; variations are skipped.
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>> {
+    let mut games = Vec::<PgnGame>::new();
+    let mut tags = Vec::<(String, String)>::new();
+    let mut movetext = String::new();
+
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with('%') {
+            continue;
+        }
+        if line.starts_with('[') && movetext.trim().is_empty() {
+            tags.push(parse_tag(line)?);
+            continue;
+        }
+        if line.starts_with('[') {
+            games.push(read_game(&tags, &movetext).with_context(|| format!("In game {}", games.len() + 1))?);
+            tags = vec![parse_tag(line)?];
+            movetext.clear();
+            continue;
+        }
+        movetext.push_str(line);
+        movetext.push('\n');
+    }
+    if !tags.is_empty() || !movetext.trim().is_empty() {
+        games.push(read_game(&tags, &movetext).with_context(|| format!("In game {}", games.len() + 1))?);
+    }
+
+    Ok(games)
+}
+
+fn parse_tag(line: &str) -> Result<(String, String)> {
+    let inner = line.trim_start_matches('[').trim_end_matches(']').trim();
+    let (key, value) = inner.split_once(' ').ok_or_else(|| anyhow!("Invalid tag: {}", line))?;
+    let value = value.trim().trim_start_matches('"').trim_end_matches('"');
+    Ok((key.to_string(), value.replace("\\\"", "\"").replace("\\\\", "\\")))
+}
+
+fn read_game(tags: &[(String, String)], movetext: &str) -> Result<PgnGame> {
+    let start = match tags.iter().find(|(key, _)| key == "FEN") {
+        Some((_, fen)) => Board::from_fen(fen)?,
+        None => Board::new(),
+    };
+    let mut game = PgnGame { tags: tags.to_vec(), board: start, comments: BTreeMap::new(), nags: BTreeMap::new() };
+
+    for token in movetext_tokens(movetext) {
+        if let Some(comment) = token.strip_prefix('{') {
+            let history = game.board.get_history();
+            if !history.is_empty() {
+                let text = game.comments.entry(history.len() - 1).or_default();
+                if !text.is_empty() { text.push(' '); }
+                text.push_str(comment.trim());
+            }
+            continue;
+        }
+        if let Some(nag) = token.strip_prefix('$') {
+            let ply = game.board.get_history().len();
+            if let (Ok(nag), Some(ply)) = (nag.parse::<u8>(), ply.checked_sub(1)) {
+                game.nags.entry(ply).or_default().push(nag);
+            }
+            continue;
+        }
+        if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) {
+            if game.tag("Result").is_none() {
+                game.set_tag("Result", &token);
+            }
+            break;
+        }
+        let m = parse_san(&token, &game.board)?;
+        game.board.play(m)?;
+        game.board.next_turn();
+        // `!`, `?` and their pairs after a move are the same as the first six NAGs.
+        let suffix = token.trim_start_matches(|c: char| c != '!' && c != '?');
+        if let Some(nag) = ["!", "?", "!!", "??", "!?", "?!"].iter().position(|s| *s == suffix) {
+            game.nags.entry(game.board.get_history().len() - 1).or_default().push(nag as u8 + 1);
+        }
+    }
+
+    Ok(game)
+}
+
+/// Splits movetext into moves, comments (kept with their opening brace), NAGs and the result,
+/// dropping move numbers and variations.
+fn movetext_tokens(movetext: &str) -> Vec<String> {
+    let mut tokens = Vec::<String>::new();
+    let mut depth = 0;
+    let mut in_comment = false;
+    let mut in_line_comment = false;
+    let mut current = String::new();
+    let mut comment = String::new();
+
+    for c in movetext.chars() {
+        if in_line_comment {
+            if c == '\n' { in_line_comment = false; }
+            continue;
+        }
+        if in_comment {
+            if c == '}' {
+                in_comment = false;
+                let text = std::mem::take(&mut comment);
+                if depth == 0 { tokens.push(text); }
+            } else {
+                comment.push(c);
+            }
+            continue;
+        }
+        match c {
+            '{' => {
+                in_comment = true;
+                push_token(&mut tokens, &mut current, depth);
+                comment.push('{');
+            },
+            ';' => { in_line_comment = true; push_token(&mut tokens, &mut current, depth); },
+            '(' => { push_token(&mut tokens, &mut current, depth); depth += 1; },
+            ')' => { push_token(&mut tokens, &mut current, depth); depth -= 1; },
+            c if c.is_whitespace() => push_token(&mut tokens, &mut current, depth),
+            c => current.push(c),
+        }
+    }
+    push_token(&mut tokens, &mut current, depth);
+
+    tokens
+}
+
+fn push_token(tokens: &mut Vec<String>, current: &mut String, depth: i32) {
+    if current.is_empty() {
+        return;
+    }
+    let token = std::mem::take(current);
+    if depth > 0 {
+        return;
+    }
+    if ["1-0", "0-1", "1/2-1/2", "*"].contains(&token.as_str()) || token.starts_with('$') {
+        tokens.push(token);
+        return;
+    }
+
+    // Move numbers may be glued to the move, as in "1.e4" or "12...Nf6".
+    let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    if !token.is_empty() {
+        tokens.push(token.to_string());
+    }
+}
@@ -0,0 +1,68 @@
+use std::{
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread::{self, JoinHandle},
+};
+use super::{
+    board::Board,
+    engine::{search_lines, Limits, SearchResult},
+    tt::TranspositionTable,
+    utils::Move,
+    zobrist::polyglot_key,
+};
+
+// Pondering: thinking on the opponent's time. Once the engine has moved, it takes the reply its
+// principal variation expects and searches the position after it on a thread of its own while the
+// opponent thinks. If the opponent plays that reply (a ponder hit), the search is already done or
+// well on its way, and its move is played. Any other reply (a miss) throws the search away, and the
+// engine searches the real position as usual.
+
+/// Table size for a ponder search.
+const PONDER_HASH_MB: usize = 16;
+
+/// A search running in the background on the position after the expected reply.
+pub struct Ponder {
+    /// The reply the search assumes.
+    pub expected: Move,
+    /// The position searched.
+    key: u64,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<Option<SearchResult>>>,
+}
+
+impl Ponder {
+    /// Starts searching the position after `expected` is played on `board` to `depth`, unless the
+    /// reply can't be played or ends the game.
+    pub fn start(board: &Board, expected: Move, depth: u32) -> Option<Self> {
+        let mut child = board.clone();
+        child.play(expected).ok()?;
+        child.next_turn();
+
+        let key = polyglot_key(&child);
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let handle = thread::spawn(move || {
+            let limits = Limits { stop: Some(&thread_stop), ..Limits::depth(depth) };
+            let analysis = search_lines(&child, limits, 1, 1, &TranspositionTable::new(PONDER_HASH_MB), |_| { });
+            analysis.lines.into_iter().next().filter(|line| line.best_move.is_some())
+        });
+        Some(Ponder { expected, key, stop, handle: Some(handle) })
+    }
+
+    /// On a hit, when `board` is the position being searched, waits for the search to finish and
+    /// gives its result. On a miss, stops it and gives nothing.
+    pub fn finish(mut self, board: &Board) -> Option<SearchResult> {
+        let hit = polyglot_key(board) == self.key;
+        if !hit {
+            self.stop.store(true, Ordering::Relaxed);
+        }
+        let result = self.handle.take()?.join().ok().flatten();
+        if hit { result } else { None }
+    }
+}
+
+impl Drop for Ponder {
+    /// A ponder search nobody waits for any more, e.g. when the game is left, ends on its own.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
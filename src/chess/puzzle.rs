@@ -0,0 +1,416 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use anyhow::{anyhow, Context, Result};
+use colored::*;
+use super::{
+    board::Board,
+    checker::{outcome, Outcome},
+    correspondence::format_timestamp,
+    interpret::parse_uci,
+    render::{Orientation, RenderOptions},
+    san::{parse_san, square_name, to_san},
+    utils::{Move, Player},
+    zobrist::polyglot_key,
+};
+use crate::input::get_input;
+
+// Puzzles are read from CSV laid out like the Lichess puzzle database:
+//
+//   PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+//   00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,e8d7 a2e6 d7d8 f7f8,1760,...
+//
+// The FEN is the position before the opponent's move; the moves are UCI, starting with that move,
+// then alternating between the solver and the opponent.
+//
+// Attempts are appended to a history file, one per line:
+//
+//   2026-10-19T09:30:00Z 00sHx solved 1760 1516
+//
+// giving the time, the puzzle, the outcome, the puzzle's rating and the solver's rating after it.
+
+pub const HISTORY_FILE: &str = "puzzles.history";
+const STARTING_RATING: i32 = 1500;
+/// How far one attempt moves the solver's rating, as in Elo.
+const K_FACTOR: f64 = 32.0;
+
+pub struct Puzzle {
+    pub id: String,
+    pub fen: String,
+    /// The opponent's first move and then the solution, in UCI.
+    pub moves: Vec<String>,
+    pub rating: i32,
+    pub themes: Vec<String>,
+}
+
+/// What a move played against the solution amounts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The expected move, with more of the line to come.
+    Correct,
+    /// The end of the line, or a mate the solution didn't list.
+    Solved,
+    Wrong,
+}
+
+impl Puzzle {
+    /// The position the solver faces: the FEN with the opponent's first move played.
+    pub fn start(&self) -> Result<Board> {
+        let mut board = Board::from_fen(&self.fen)?;
+        let first = self.moves.first().ok_or_else(|| anyhow!("Puzzle {} has no moves!", self.id))?;
+        play_uci(&mut board, first)?;
+        Ok(board)
+    }
+
+    /// Plays the whole line, to check it.
+    pub fn replay(&self) -> Result<Board> {
+        let mut board = Board::from_fen(&self.fen)?;
+        for uci in &self.moves {
+            play_uci(&mut board, uci)?;
+        }
+        Ok(board)
+    }
+
+    /// The side the solver plays.
+    pub fn solver(&self) -> Result<Player> {
+        Ok(self.start()?.get_turn())
+    }
+
+    /// Checks the solver's move against the solution, where `ply` is the index of the move that
+    /// was expected. Moves are compared by the position they reach, so any way of writing a move
+    /// (or castling) counts, and any mate is accepted.
+    pub fn check(&self, board: &Board, ply: usize, m: Move) -> Verdict {
+        let mut played = board.clone();
+        if played.play(m).is_err() {
+            return Verdict::Wrong;
+        }
+        played.next_turn();
+        if matches!(outcome(&played), Some(Outcome::Checkmate(_))) {
+            return Verdict::Solved;
+        }
+
+        let Some(expected) = self.moves.get(ply) else { return Verdict::Wrong; };
+        let mut solution = board.clone();
+        if play_uci(&mut solution, expected).is_err() || polyglot_key(&solution) != polyglot_key(&played) {
+            return Verdict::Wrong;
+        }
+        if ply + 1 >= self.moves.len() { Verdict::Solved } else { Verdict::Correct }
+    }
+}
+
+/// Reads puzzles from CSV text, with or without the header line.
+pub fn parse_csv(text: &str) -> Result<Vec<Puzzle>> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()).peekable();
+
+    let mut columns = [0, 1, 2, 3, 7];
+    if let Some((_, header)) = lines.peek() {
+        if header.starts_with("PuzzleId") {
+            let names: Vec<&str> = header.split(',').map(str::trim).collect();
+            for (column, name) in columns.iter_mut().zip(["PuzzleId", "FEN", "Moves", "Rating", "Themes"]) {
+                *column = names.iter().position(|n| *n == name).unwrap_or(usize::MAX);
+            }
+            lines.next();
+        }
+    }
+    let [id, fen, moves, rating, themes] = columns;
+
+    let mut puzzles = Vec::<Puzzle>::new();
+    for (number, line) in lines {
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim().trim_matches('"')).collect();
+        let field = |column: usize| fields.get(column).copied().unwrap_or("");
+        let puzzle = Puzzle {
+            id: field(id).to_string(),
+            fen: field(fen).to_string(),
+            moves: field(moves).split_whitespace().map(str::to_string).collect(),
+            rating: field(rating).parse().unwrap_or(STARTING_RATING),
+            themes: field(themes).split_whitespace().map(str::to_string).collect(),
+        };
+        if puzzle.id.is_empty() || puzzle.moves.len() < 2 {
+            return Err(anyhow!("Line {} is not a puzzle: {}", number + 1, line));
+        }
+        // The opponent's move and then pairs of solver move and reply, ending on the solver's move.
+        if !puzzle.moves.len().is_multiple_of(2) {
+            return Err(anyhow!("Line {} ends on the opponent's move: {}", number + 1, line));
+        }
+        puzzle.replay().with_context(|| format!("Line {} doesn't play out: {}", number + 1, line))?;
+        puzzles.push(puzzle);
+    }
+    Ok(puzzles)
+}
+
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Puzzle>> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+    parse_csv(&text).with_context(|| format!("In {}", path.display()))
+}
+
+/// One line of the history.
+pub struct Attempt {
+    pub time: String,
+    pub id: String,
+    pub solved: bool,
+    pub puzzle_rating: i32,
+    pub rating: i32,
+}
+
+/// Every attempt so far, oldest first.
+#[derive(Default)]
+pub struct History {
+    pub attempts: Vec<Attempt>,
+}
+
+impl History {
+    /// Reads the history; a file that doesn't exist yet is an empty history.
+    pub fn load(path: impl AsRef<Path>) -> Result<History> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(History::default());
+        }
+        let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path.display()))?;
+        let mut attempts = Vec::<Attempt>::new();
+        for (number, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            let attempt = match parts.as_slice() {
+                [time, id, outcome @ ("solved" | "failed"), puzzle_rating, rating] => Attempt {
+                    time: time.to_string(),
+                    id: id.to_string(),
+                    solved: *outcome == "solved",
+                    puzzle_rating: puzzle_rating.parse()?,
+                    rating: rating.parse()?,
+                },
+                _ => { return Err(anyhow!("Line {} of {} is not an attempt: {}", number + 1, path.display(), line)); },
+            };
+            attempts.push(attempt);
+        }
+        Ok(History { attempts })
+    }
+
+    pub fn rating(&self) -> i32 {
+        self.attempts.last().map(|attempt| attempt.rating).unwrap_or(STARTING_RATING)
+    }
+
+    pub fn solved(&self) -> usize {
+        self.attempts.iter().filter(|attempt| attempt.solved).count()
+    }
+
+    pub fn tried(&self, id: &str) -> bool {
+        self.attempts.iter().any(|attempt| attempt.id == id)
+    }
+
+    /// Adds an attempt, moving the rating the way Elo does against the puzzle's rating, and
+    /// appends it to the file.
+    pub fn record(&mut self, path: impl AsRef<Path>, puzzle: &Puzzle, solved: bool) -> Result<&Attempt> {
+        let rating = self.rating();
+        let expected = 1.0 / (1.0 + 10f64.powf((puzzle.rating - rating) as f64 / 400.0));
+        let score = if solved { 1.0 } else { 0.0 };
+        let attempt = Attempt {
+            time: format_timestamp(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)),
+            id: puzzle.id.clone(),
+            solved,
+            puzzle_rating: puzzle.rating,
+            rating: rating + (K_FACTOR * (score - expected)).round() as i32,
+        };
+
+        let path = path.as_ref();
+        let mut file = OpenOptions::new().create(true).append(true).open(path)
+            .with_context(|| format!("Could not write {}", path.display()))?;
+        writeln!(
+            file, "{} {} {} {} {}",
+            attempt.time, attempt.id, if solved { "solved" } else { "failed" }, attempt.puzzle_rating, attempt.rating,
+        )?;
+
+        self.attempts.push(attempt);
+        Ok(self.attempts.last().unwrap())
+    }
+}
+
+/// Picks the untried puzzle rated closest to the solver.
+pub fn next_puzzle<'a>(puzzles: &'a [Puzzle], history: &History) -> Option<&'a Puzzle> {
+    let rating = history.rating();
+    puzzles.iter()
+        .filter(|puzzle| !history.tried(&puzzle.id))
+        .min_by_key(|puzzle| (puzzle.rating - rating).abs())
+}
+
+/// Plays puzzles in the terminal until they run out or the solver quits.
+pub fn train(puzzles: &[Puzzle], history_path: &Path) -> Result<()> {
+    let mut history = History::load(history_path)?;
+    let mut solo = puzzles.len() == 1;
+
+    while let Some(puzzle) = if solo { puzzles.first() } else { next_puzzle(puzzles, &history) } {
+        solo = false;
+        let Some(solved) = solve(puzzle)? else { break; };
+        let before = history.rating();
+        let attempt = history.record(history_path, puzzle, solved)?;
+        println!("Rating {} ({:+}). Press enter for the next puzzle, or q to stop...", attempt.rating, attempt.rating - before);
+        if get_input().trim().eq_ignore_ascii_case("q") { break; }
+    }
+
+    println!("{} of {} puzzles solved; rating {}.", history.solved(), history.attempts.len(), history.rating());
+    Ok(())
+}
+
+/// Plays one puzzle. `None` if the solver quit part way.
+fn solve(puzzle: &Puzzle) -> Result<Option<bool>> {
+    let mut board = puzzle.start()?;
+    let solver = board.get_turn();
+    let options = RenderOptions {
+        orientation: match solver { Player::White => Orientation::White, Player::Black => Orientation::Black },
+        ..RenderOptions::default()
+    };
+    let mut ply = 1;
+    let mut message = String::new();
+    let mut hinted = false;
+
+    loop {
+        clearscr!();
+        board.print_with(&options);
+        println!(
+            "Puzzle {} (rated {}). {} to move; find the best move.",
+            puzzle.id.bold(), puzzle.rating, match solver { Player::White => "White", Player::Black => "Black" },
+        );
+        if !message.is_empty() {
+            println!("{}", message);
+        }
+        println!("Your move, or hint, skip or q:");
+
+        let input = get_input();
+        let input = input.trim();
+        match input.to_lowercase().as_str() {
+            "q" | "quit" => { return Ok(None); },
+            "skip" => {
+                show_solution(puzzle, &board, ply);
+                return Ok(Some(false));
+            },
+            "hint" => {
+                let expected = puzzle.moves.get(ply).ok_or_else(|| anyhow!("Puzzle {} has no move {}!", puzzle.id, ply + 1))?;
+                let m = Move::from_uci(expected)?;
+                message = format!("Hint: the piece on {} moves. The puzzle now counts as failed.", square_name(m.from));
+                hinted = true;
+                continue;
+            },
+            _ => { },
+        }
+
+        let m = match parse_uci(input, &board).or_else(|_| parse_san(input, &board)) {
+            Ok(m) => m,
+            Err(error) => {
+                message = error.to_string();
+                continue;
+            },
+        };
+
+        match puzzle.check(&board, ply, m) {
+            Verdict::Wrong => {
+                println!("{} is not it.", to_san(&board, &m).red());
+                show_solution(puzzle, &board, ply);
+                return Ok(Some(false));
+            },
+            Verdict::Solved => {
+                println!("{} Solved!", to_san(&board, &m).green());
+                return Ok(Some(!hinted));
+            },
+            Verdict::Correct => {
+                let san = to_san(&board, &m);
+                board.play(m)?;
+                board.next_turn();
+                let reply = puzzle.moves.get(ply + 1).ok_or_else(|| anyhow!("Puzzle {} has no reply to move {}!", puzzle.id, ply + 1))?;
+                let reply = parse_uci(reply, &board)?;
+                let reply_san = to_san(&board, &reply);
+                board.play(reply)?;
+                board.next_turn();
+                message = format!("{} is right. The reply: {}", san.green(), reply_san);
+                ply += 2;
+            },
+        }
+    }
+}
+
+/// Prints the rest of the solution from the given point.
+fn show_solution(puzzle: &Puzzle, board: &Board, ply: usize) {
+    let mut replay = board.clone();
+    let mut line = Vec::<String>::new();
+    for uci in puzzle.moves.iter().skip(ply) {
+        let Ok(m) = parse_uci(uci, &replay) else { break; };
+        line.push(to_san(&replay, &m));
+        if replay.play(m).is_err() { break; }
+        replay.next_turn();
+    }
+    println!("The solution: {}", line.join(" "));
+}
+
+fn play_uci(board: &mut Board, uci: &str) -> Result<()> {
+    let m = parse_uci(uci, board)?;
+    board.play(m)?;
+    board.next_turn();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black plays c6, then White mates on the back rank with either rook.
+    const BACK_RANK: &str = "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags
+abc12,7k/2p3pp/8/8/8/8/1R6/R5K1 b - - 0 1,c7c6 a1a8,1500,80,90,100,mate mateIn1 backRankMate,,
+";
+
+    #[test]
+    fn csv_lines_are_read_by_header_name() {
+        let puzzles = parse_csv(BACK_RANK).unwrap();
+        assert_eq!(puzzles.len(), 1);
+        assert_eq!(puzzles[0].id, "abc12");
+        assert_eq!(puzzles[0].moves, vec!["c7c6", "a1a8"]);
+        assert_eq!(puzzles[0].rating, 1500);
+        assert_eq!(puzzles[0].themes, vec!["mate", "mateIn1", "backRankMate"]);
+        assert!(matches!(puzzles[0].solver(), Ok(Player::White)));
+    }
+
+    #[test]
+    fn broken_csv_lines_are_refused() {
+        // Ends on the opponent's move.
+        assert!(parse_csv("abc12,7k/2p3pp/8/8/8/8/1R6/R5K1 b - - 0 1,c7c6 a1a8 h7h6,1500").is_err());
+        // Only the opponent's move.
+        assert!(parse_csv("abc12,7k/2p3pp/8/8/8/8/1R6/R5K1 b - - 0 1,c7c6,1500").is_err());
+        // A move that isn't legal.
+        assert!(parse_csv("abc12,7k/2p3pp/8/8/8/8/1R6/R5K1 b - - 0 1,c7c6 a1h8,1500").is_err());
+    }
+
+    #[test]
+    fn any_mate_solves_and_other_moves_fail() {
+        let puzzle = parse_csv(BACK_RANK).unwrap().remove(0);
+        let board = puzzle.start().unwrap();
+        let m = |text: &str| parse_uci(text, &board).unwrap();
+
+        assert_eq!(puzzle.check(&board, 1, m("a1a8")), Verdict::Solved);
+        assert_eq!(puzzle.check(&board, 1, m("b2b8")), Verdict::Solved);
+        assert_eq!(puzzle.check(&board, 1, m("g1f2")), Verdict::Wrong);
+        assert_eq!(puzzle.check(&board, 1, m("a1a7")), Verdict::Wrong);
+    }
+
+    #[test]
+    fn ratings_move_the_way_elo_does() {
+        let path = std::env::temp_dir().join(format!("chess-puzzle-history-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut puzzle = parse_csv(BACK_RANK).unwrap().remove(0);
+        let mut history = History::default();
+
+        // An even match is worth half of K either way.
+        assert_eq!(history.record(&path, &puzzle, true).unwrap().rating, 1516);
+        puzzle.rating = 1516;
+        assert_eq!(history.record(&path, &puzzle, false).unwrap().rating, 1500);
+        // Failing a puzzle rated far above costs little; solving it gains nearly all of K.
+        puzzle.rating = 2300;
+        assert_eq!(history.record(&path, &puzzle, false).unwrap().rating, 1500);
+        assert_eq!(history.record(&path, &puzzle, true).unwrap().rating, 1532);
+
+        let reloaded = History::load(&path).unwrap();
+        assert_eq!(reloaded.attempts.len(), 4);
+        assert_eq!(reloaded.rating(), 1532);
+        assert_eq!(reloaded.solved(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
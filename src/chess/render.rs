@@ -0,0 +1,265 @@
+use colored::*;
+use super::{board::BOARD_LEN, utils::{Piece, PieceType, Player, PlayerPiece}};
+
+pub const THEME_NAMES: [&str; 4] = ["classic", "wood", "ocean", "mono"];
+
+/// Which side of the board is drawn at the bottom of the screen.
+#[derive(Clone, Copy)]
+pub enum Orientation {
+    White,
+    Black,
+    SideToMove,
+}
+
+/// How the board shows which side controls each square.
+#[derive(Clone, Copy)]
+pub enum ControlView {
+    /// Tint squares toward the side with more attackers.
+    Shade,
+    /// Write each side's number of attackers on the square.
+    Counts,
+}
+
+#[derive(Clone, Copy)]
+pub enum Glyphs {
+    Ascii,
+    Unicode,
+}
+
+/// Colors used when drawing the board. A square color of `None` leaves the
+/// terminal background alone, a piece color of `None` uses the terminal foreground.
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub light_square: Option<Color>,
+    pub dark_square: Option<Color>,
+    pub white_piece: Option<Color>,
+    pub black_piece: Option<Color>,
+    pub last_move: Option<Color>,
+    pub highlight: Option<Color>,
+}
+
+#[derive(Clone, Copy)]
+pub struct RenderOptions {
+    pub orientation: Orientation,
+    pub glyphs: Glyphs,
+    pub theme: Theme,
+    pub compact: bool,
+    pub move_list: bool,
+    /// Squares to mark, e.g. the destinations of a piece asked about with `moves`.
+    pub highlights: [bool; BOARD_LEN],
+    /// Whether to work out and underline the pieces that lose material to a capture.
+    pub mark_hanging: bool,
+    pub hanging: [bool; BOARD_LEN],
+    pub control_view: Option<ControlView>,
+    /// White's and Black's attackers of every square, x-rays included; filled in when the board is
+    /// drawn with a `control_view`.
+    pub control: [(u8, u8); BOARD_LEN],
+}
+
+impl Theme {
+    pub fn classic() -> Self {
+        Self {
+            light_square: None,
+            dark_square: None,
+            white_piece: None,
+            black_piece: Some(Color::Green),
+            last_move: Some(Color::TrueColor { r: 92, g: 91, b: 91 }),
+            highlight: Some(Color::TrueColor { r: 170, g: 60, b: 60 }),
+        }
+    }
+
+    pub fn wood() -> Self {
+        Self {
+            light_square: Some(Color::TrueColor { r: 240, g: 217, b: 181 }),
+            dark_square: Some(Color::TrueColor { r: 181, g: 136, b: 99 }),
+            white_piece: Some(Color::TrueColor { r: 255, g: 255, b: 255 }),
+            black_piece: Some(Color::TrueColor { r: 0, g: 0, b: 0 }),
+            last_move: Some(Color::TrueColor { r: 205, g: 210, b: 106 }),
+            highlight: Some(Color::TrueColor { r: 214, g: 90, b: 70 }),
+        }
+    }
+
+    pub fn ocean() -> Self {
+        Self {
+            light_square: Some(Color::TrueColor { r: 222, g: 227, b: 230 }),
+            dark_square: Some(Color::TrueColor { r: 140, g: 162, b: 173 }),
+            white_piece: Some(Color::TrueColor { r: 255, g: 255, b: 255 }),
+            black_piece: Some(Color::TrueColor { r: 20, g: 30, b: 60 }),
+            last_move: Some(Color::TrueColor { r: 130, g: 200, b: 160 }),
+            highlight: Some(Color::TrueColor { r: 230, g: 120, b: 90 }),
+        }
+    }
+
+    pub fn mono() -> Self {
+        Self {
+            light_square: None,
+            dark_square: None,
+            white_piece: None,
+            black_piece: None,
+            last_move: None,
+            highlight: None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "classic" => Some(Theme::classic()),
+            "wood" => Some(Theme::wood()),
+            "ocean" => Some(Theme::ocean()),
+            "mono" => Some(Theme::mono()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            orientation: Orientation::White,
+            glyphs: Glyphs::Ascii,
+            theme: Theme::classic(),
+            compact: false,
+            move_list: true,
+            highlights: [false; BOARD_LEN],
+            mark_hanging: true,
+            hanging: [false; BOARD_LEN],
+            control_view: None,
+            control: [(0, 0); BOARD_LEN],
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Returns the player whose pieces are drawn at the bottom of the board.
+    pub fn bottom(&self, turn: Player) -> Player {
+        match self.orientation {
+            Orientation::White => Player::White,
+            Orientation::Black => Player::Black,
+            Orientation::SideToMove => turn,
+        }
+    }
+
+    /// Returns the board index shown at the given screen row and column.
+    pub fn index_at(&self, turn: Player, row: usize, col: usize) -> usize {
+        match self.bottom(turn) {
+            Player::White => (row * 8) + col,
+            Player::Black => ((7 - row) * 8) + (7 - col),
+        }
+    }
+
+    pub fn piece_string(&self, piece: PlayerPiece) -> ColoredString {
+        let glyph = match self.glyphs {
+            Glyphs::Ascii => piece.to_char().to_string(),
+            Glyphs::Unicode => piece.to_unicode().to_string(),
+        };
+        let color = match piece.player {
+            Player::White => self.theme.white_piece,
+            Player::Black => self.theme.black_piece,
+        };
+        match color {
+            None => glyph.bold(),
+            Some(c) => glyph.color(c).bold(),
+        }
+    }
+
+    /// Renders the contents of one square, `width` characters wide, on the square's background.
+    pub fn square_string(&self, piece: Piece, index: usize, is_last_move: bool, is_moved: bool, width: usize) -> String {
+        let background = if self.highlights[index] && self.theme.highlight.is_some() {
+            self.theme.highlight
+        } else if is_moved && self.theme.last_move.is_some() {
+            self.theme.last_move
+        } else {
+            self.square_background(index)
+        };
+
+        let glyph = match piece {
+            Piece::Piece(p) if self.hanging[index] => match self.theme.highlight {
+                Some(c) => self.piece_string(p).color(c).underline(),
+                None => self.piece_string(p).underline(),
+            },
+            Piece::Piece(p) => self.piece_string(p),
+            Piece::None => {
+                if self.highlights[index] {
+                    "*".bold()
+                } else if is_last_move {
+                    match self.theme.last_move {
+                        Some(_) => "#".truecolor(138, 138, 138),
+                        None => "#".normal(),
+                    }
+                } else if let (Some(ControlView::Counts), true) = (self.control_view, self.compact) {
+                    match self.control[index] {
+                        (0, 0) => ".".normal(),
+                        (white, black) if white > black => (white - black).to_string().bold(),
+                        (white, black) if black > white => (black - white).to_string().green(),
+                        _ => "=".normal(),
+                    }
+                } else if self.compact && background.is_none() {
+                    ".".normal()
+                } else {
+                    " ".normal()
+                }
+            }
+        };
+
+        let pad = " ".repeat(width / 2);
+        match background {
+            None => format!("{}{}{}", pad, glyph, pad),
+            Some(bg) => format!("{}{}{}", pad.on_color(bg), glyph.on_color(bg), pad.on_color(bg)),
+        }
+    }
+
+    /// Fills the bottom edge of a square so shaded squares stay solid in the boxed layout.
+    pub fn square_floor(&self, index: usize) -> String {
+        let background = self.square_background(index);
+        match background {
+            None => "_____".to_string(),
+            Some(bg) => "_____".on_color(bg).to_string(),
+        }
+    }
+
+    /// The top line of a square in the boxed layout, holding the attacker counts when they are shown,
+    /// White's on the left and Black's on the right.
+    pub fn square_ceiling(&self, index: usize) -> String {
+        let background = self.square_background(index);
+        let text = match (self.control_view, self.control[index]) {
+            (Some(ControlView::Counts), (0, 0)) | (Some(ControlView::Shade) | None, _) => "     ".normal(),
+            (Some(ControlView::Counts), (white, black)) => format!("{:<2} {:>2}", count(white), count(black)).normal(),
+        };
+        match background {
+            None => text.to_string(),
+            Some(bg) => text.on_color(bg).to_string(),
+        }
+    }
+
+    /// The square's own color, or its shade of control when control is shaded.
+    fn square_background(&self, index: usize) -> Option<Color> {
+        let base = if is_light_square(index) { self.theme.light_square } else { self.theme.dark_square };
+        let Some(ControlView::Shade) = self.control_view else { return base; };
+        let (white, black) = self.control[index];
+        let strength = white.abs_diff(black).min(3) * 40;
+        match white.cmp(&black) {
+            std::cmp::Ordering::Greater => Some(Color::TrueColor { r: 200 - strength, g: 200 - strength / 2, b: 230 }),
+            std::cmp::Ordering::Less => Some(Color::TrueColor { r: 230, g: 190 - strength, b: 170 - strength }),
+            std::cmp::Ordering::Equal if white > 0 => Some(Color::TrueColor { r: 200, g: 200, b: 200 }),
+            std::cmp::Ordering::Equal => base,
+        }
+    }
+
+    pub fn captured_string(&self, pieces: &[PieceType], owner: Player) -> String {
+        let mut output = String::new();
+        for piece in pieces {
+            output.push_str(&format!("{} ", self.piece_string(PlayerPiece::new(*piece, owner))));
+        }
+        output
+    }
+}
+
+/// An attacker count, blank for none.
+fn count(n: u8) -> String {
+    if n == 0 { String::new() } else { n.to_string() }
+}
+
+/// a8 (index 0) is a light square.
+fn is_light_square(index: usize) -> bool {
+    ((index / 8) + (index % 8)).is_multiple_of(2)
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use super::{
+    board::Board,
+    engine::{search, MATE},
+    pgn::PgnGame,
+    san::to_san,
+    utils::{Move, Player},
+};
+
+/// Scores past this many centipawns are all as good as won; mates count as this much.
+const EVAL_CAP: i32 = 1000;
+
+/// How a move compares with the engine's choice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Class {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl Class {
+    /// Classes by centipawns lost: under 50 is good, then 50, 100 and 300 for the rest.
+    fn from_loss(loss: i32) -> Class {
+        match loss {
+            ..=0 => Class::Best,
+            1..=49 => Class::Good,
+            50..=99 => Class::Inaccuracy,
+            100..=299 => Class::Mistake,
+            _ => Class::Blunder,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Best => "best",
+            Class::Good => "good",
+            Class::Inaccuracy => "inaccuracy",
+            Class::Mistake => "mistake",
+            Class::Blunder => "blunder",
+        }
+    }
+
+    /// The annotation glyph for the class, if it gets one: `$6` (?!), `$2` (?) or `$4` (??).
+    pub fn nag(self) -> Option<u8> {
+        match self {
+            Class::Inaccuracy => Some(6),
+            Class::Mistake => Some(2),
+            Class::Blunder => Some(4),
+            _ => None,
+        }
+    }
+}
+
+/// One move of the game, judged.
+pub struct MoveReview {
+    pub player: Player,
+    pub san: String,
+    /// The engine's move in the position, in SAN.
+    pub best: Option<String>,
+    /// Scores from White's point of view before and after the move, capped at `EVAL_CAP`.
+    pub eval_before: i32,
+    pub eval_after: i32,
+    /// Centipawns the move gave away compared with the engine's move.
+    pub loss: i32,
+    /// 0 to 100, from how much the move changed the mover's winning chances.
+    pub accuracy: f64,
+    pub class: Class,
+}
+
+/// One side's totals.
+#[derive(Default)]
+pub struct PlayerSummary {
+    pub moves: usize,
+    pub average_loss: f64,
+    pub accuracy: f64,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+pub struct Review {
+    pub moves: Vec<MoveReview>,
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+/// Replays the game and searches every position once, at `depth`. The loss of a move is the score
+/// of the engine's move minus the score of the one played, both from the mover's side.
+pub fn review(game: &Board, depth: u32) -> Result<Review> {
+    let mut board = Board::from_fen(game.get_start_fen())?;
+    let mut moves = Vec::<MoveReview>::new();
+    let mut before = analyse(&board, depth);
+
+    for m in game.get_history() {
+        let player = board.get_turn();
+        let san = to_san(&board, m);
+        let best = before.1.map(|best| to_san(&board, &best));
+        board.play(*m)?;
+        board.next_turn();
+        // The played move is scored by the search of the position it leads to, which is also the
+        // next move's `before`. That sees it one ply further than the engine's move was seen.
+        let after = analyse(&board, depth);
+        let kept = -after.0;
+
+        let loss = if before.1 == Some(*m) { 0 } else { (before.0 - kept).max(0) };
+        let accuracy = move_accuracy(win_chance(before.0), win_chance(kept.min(before.0)));
+        let white_view = |score: i32| match player { Player::White => score, Player::Black => -score };
+
+        moves.push(MoveReview {
+            player,
+            san,
+            best,
+            eval_before: white_view(before.0),
+            eval_after: white_view(kept),
+            loss,
+            accuracy,
+            class: Class::from_loss(loss),
+        });
+        before = after;
+    }
+
+    let summary = |player: Player| {
+        let mine: Vec<&MoveReview> = moves.iter().filter(|r| std::mem::discriminant(&r.player) == std::mem::discriminant(&player)).collect();
+        if mine.is_empty() {
+            return PlayerSummary::default();
+        }
+        let count = |class: Class| mine.iter().filter(|r| r.class == class).count();
+        PlayerSummary {
+            moves: mine.len(),
+            average_loss: mine.iter().map(|r| r.loss as f64).sum::<f64>() / mine.len() as f64,
+            accuracy: mine.iter().map(|r| r.accuracy).sum::<f64>() / mine.len() as f64,
+            inaccuracies: count(Class::Inaccuracy),
+            mistakes: count(Class::Mistake),
+            blunders: count(Class::Blunder),
+        }
+    };
+    let (white, black) = (summary(Player::White), summary(Player::Black));
+
+    Ok(Review { moves, white, black })
+}
+
+/// Writes the review into the game: the score after every move as `[%eval 0.35]`, a note and a
+/// glyph on inaccuracies, mistakes and blunders, and the accuracies as tags.
+pub fn annotate(game: &mut PgnGame, review: &Review) {
+    for (ply, reviewed) in review.moves.iter().enumerate() {
+        let mut text = format!("[%eval {:.2}]", reviewed.eval_after as f64 / 100.0);
+        if let Some(nag) = reviewed.class.nag() {
+            let nags = game.nags.entry(ply).or_default();
+            nags.retain(|n| !(1..=6).contains(n));
+            nags.push(nag);
+
+            let mut class = reviewed.class.name().to_string();
+            class[..1].make_ascii_uppercase();
+            text.push_str(&format!(" {}.", class));
+            if let Some(best) = &reviewed.best {
+                text.push_str(&format!(" {} was best.", best));
+            }
+        }
+
+        let comment = game.comments.entry(ply).or_default();
+        if !comment.is_empty() {
+            comment.push(' ');
+        }
+        comment.push_str(&text);
+    }
+
+    game.set_tag("WhiteAccuracy", &format!("{:.1}", review.white.accuracy));
+    game.set_tag("BlackAccuracy", &format!("{:.1}", review.black.accuracy));
+}
+
+/// The position's score for the side to move, capped, and the engine's move.
+fn analyse(board: &Board, depth: u32) -> (i32, Option<Move>) {
+    let result = search(board, depth);
+    let score = if result.score.abs() > MATE - 1000 { result.score.signum() * EVAL_CAP } else { result.score };
+    (score.clamp(-EVAL_CAP, EVAL_CAP), result.best_move)
+}
+
+/// Chance of winning, 0 to 100, for a score in centipawns; the curve Lichess fitted to its games.
+fn win_chance(score: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * score as f64).exp()) - 1.0)
+}
+
+/// Accuracy of one move from the winning chances before and after it, again after Lichess.
+fn move_accuracy(before: f64, after: f64) -> f64 {
+    (103.1668 * (-0.04354 * (before - after)).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chess::pgn::parse_pgn;
+
+    #[test]
+    fn losses_are_classed_at_50_100_and_300() {
+        assert_eq!(Class::from_loss(-20), Class::Best);
+        assert_eq!(Class::from_loss(0), Class::Best);
+        assert_eq!(Class::from_loss(49), Class::Good);
+        assert_eq!(Class::from_loss(50), Class::Inaccuracy);
+        assert_eq!(Class::from_loss(99), Class::Inaccuracy);
+        assert_eq!(Class::from_loss(100), Class::Mistake);
+        assert_eq!(Class::from_loss(299), Class::Mistake);
+        assert_eq!(Class::from_loss(300), Class::Blunder);
+    }
+
+    #[test]
+    fn accuracy_falls_with_the_winning_chances_lost() {
+        assert!((win_chance(0) - 50.0).abs() < 1e-9);
+        assert!(win_chance(EVAL_CAP) > 97.0 && win_chance(-EVAL_CAP) < 3.0);
+        assert!((win_chance(300) + win_chance(-300) - 100.0).abs() < 1e-9);
+
+        assert!(move_accuracy(50.0, 50.0) > 99.9);
+        assert!(move_accuracy(60.0, 50.0) > move_accuracy(70.0, 50.0));
+        assert_eq!(move_accuracy(100.0, 0.0), 0.0);
+    }
+
+    #[test]
+    fn a_blunder_into_mate_is_found_and_annotated() {
+        let mut game = parse_pgn("1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0").unwrap().remove(0);
+        let result = review(&game.board, 2).unwrap();
+
+        assert_eq!(result.moves.len(), 7);
+        let blunder = &result.moves[5];
+        assert_eq!(blunder.san, "Nf6");
+        assert_eq!(blunder.class, Class::Blunder);
+        assert!(blunder.best.as_deref().is_some_and(|best| best != "Nf6"));
+        assert_eq!(blunder.eval_after, EVAL_CAP);
+        assert_eq!(result.moves[6].class, Class::Best);
+        assert_eq!(result.black.blunders, 1);
+        assert!(result.black.accuracy < result.white.accuracy);
+
+        annotate(&mut game, &result);
+        assert_eq!(game.nags.get(&5), Some(&vec![4]));
+        let comment = &game.comments[&5];
+        assert!(comment.starts_with("[%eval 10.00] Blunder.") && comment.ends_with("was best."), "{}", comment);
+        assert_eq!(game.comments[&0].matches("[%eval").count(), 1);
+        assert!(game.tag("WhiteAccuracy").is_some() && game.tag("BlackAccuracy").is_some());
+    }
+}
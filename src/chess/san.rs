@@ -0,0 +1,158 @@
+use std::mem;
+use anyhow::{anyhow, Result};
+use super::{
+    board::{Board, ROW_LEN},
+    checker::{is_in_check, Snapshot},
+    movement::{is_en_passant, legal_moves},
+    utils::{Castle, Move, Piece, PieceType, Player},
+};
+
+/// Writes a move in standard algebraic notation. The board is the position before the move.
+pub fn to_san(board: &Board, m: &Move) -> String {
+    let mut san = san_without_suffix(board, m);
+
+    let mut after = board.clone();
+    if after.play(*m).is_ok() {
+        after.next_turn();
+        if is_in_check(&Snapshot::new(&after), after.get_turn()) {
+            if legal_moves(&after).is_empty() {
+                san.push('#');
+            } else {
+                san.push('+');
+            }
+        }
+    }
+
+    san
+}
+
+/// Writes a move as its start and end squares, e.g. `e2e4`, `e1g1` for castling or `e7e8q` for a promotion.
+pub fn to_uci(m: &Move) -> String {
+    let mut uci = format!("{}{}", square_name(m.from), square_name(m.to));
+    if let Some(promotion) = m.promotion {
+        uci.push(promotion.to_char().to_ascii_lowercase());
+    }
+    uci
+}
+
+/// Reads a move in standard algebraic notation. Check marks and annotations such as `!?` are
+/// ignored, `0-0` is accepted for castling, and if nothing matches exactly the match is retried
+/// ignoring case, so typed input like `nf3` still works.
+pub fn parse_san(input: &str, board: &Board) -> Result<Move> {
+    let wanted = normalize_san(input);
+    if wanted.is_empty() {
+        return Err(anyhow!("You can't input nothing!"));
+    }
+
+    let candidates: Vec<(Move, String)> = legal_moves(board).into_iter()
+        .map(|m| { let san = normalize_san(&san_without_suffix(board, &m)); (m, san) })
+        .collect();
+
+    if let Some((m, _)) = candidates.iter().find(|(_, san)| *san == wanted) {
+        return Ok(*m);
+    }
+
+    let matches: Vec<_> = candidates.iter().filter(|(_, san)| san.eq_ignore_ascii_case(&wanted)).collect();
+    match matches.len() {
+        0 => Err(anyhow!("'{}' is not a legal move!", input.trim())),
+        1 => Ok(matches[0].0),
+        _ => Err(anyhow!("'{}' could mean more than one move, try capitals for pieces (e.g. Bc4)!", input.trim())),
+    }
+}
+
+fn normalize_san(input: &str) -> String {
+    input.trim()
+        .trim_end_matches(['+', '#', '!', '?'])
+        .replace("e.p.", "")
+        .replace(['=', ' '], "")
+        .replace('0', "O")
+}
+
+fn san_without_suffix(board: &Board, m: &Move) -> String {
+    match m.castle {
+        Some(Castle::Short) => "O-O".to_string(),
+        Some(Castle::Long) => "O-O-O".to_string(),
+        None => piece_move_san(board, m),
+    }
+}
+
+pub fn square_name(index: usize) -> String {
+    format!("{}{}", file_char(index), rank_char(index))
+}
+
+fn file_char(index: usize) -> char {
+    (b'a' + (index % ROW_LEN) as u8) as char
+}
+
+fn rank_char(index: usize) -> char {
+    (b'8' - (index / ROW_LEN) as u8) as char
+}
+
+fn piece_move_san(board: &Board, m: &Move) -> String {
+    let piece = match board.get_space(m.from).and_then(|p| p.extract_value()) {
+        Some(piece) => piece,
+        None => { return square_name(m.to); },
+    };
+    let is_capture = matches!(board.get_space(m.to), Some(Piece::Piece(_))) || is_en_passant(board, m);
+
+    let mut san = String::new();
+    if let PieceType::Pawn = piece {
+        if is_capture {
+            san.push(file_char(m.from));
+            san.push('x');
+        }
+        san.push_str(&square_name(m.to));
+        if let Some(promotion) = m.promotion {
+            san.push('=');
+            san.push(promotion.to_char());
+        }
+        return san;
+    }
+
+    san.push(piece.to_char());
+
+    let rivals: Vec<usize> = legal_moves(board).iter()
+        .filter(|other| other.castle.is_none() && other.to == m.to && other.from != m.from)
+        .filter(|other| {
+            board.get_space(other.from)
+                .and_then(|p| p.extract_value())
+                .is_some_and(|p| mem::discriminant(&p) == mem::discriminant(&piece))
+        })
+        .map(|other| other.from)
+        .collect();
+    if !rivals.is_empty() {
+        let shares_file = rivals.iter().any(|&from| from % ROW_LEN == m.from % ROW_LEN);
+        let shares_rank = rivals.iter().any(|&from| from / ROW_LEN == m.from / ROW_LEN);
+        if !shares_file {
+            san.push(file_char(m.from));
+        } else if !shares_rank {
+            san.push(rank_char(m.from));
+        } else {
+            san.push_str(&square_name(m.from));
+        }
+    }
+
+    if is_capture {
+        san.push('x');
+    }
+    san.push_str(&square_name(m.to));
+
+    san
+}
+
+/// Writes a line of moves in SAN with move numbers, starting from the given position.
+pub fn line_to_san(board: &Board, line: &[Move]) -> String {
+    let mut replay = board.clone();
+    let mut text = Vec::<String>::new();
+    for (i, m) in line.iter().enumerate() {
+        match replay.get_turn() {
+            Player::White => text.push(format!("{}.", replay.get_fullmove_number())),
+            Player::Black if i == 0 => text.push(format!("{}...", replay.get_fullmove_number())),
+            Player::Black => { },
+        }
+        text.push(to_san(&replay, m));
+        if replay.play(*m).is_err() { break; }
+        replay.next_turn();
+    }
+    text.join(" ")
+}
@@ -0,0 +1,111 @@
+use super::{
+    board::{Board, BOARD_LEN, ROW_LEN},
+    checker::{attackers, Snapshot},
+    control::behind,
+    eval::piece_value,
+    utils::{Move, Piece, PieceType, Player},
+};
+
+// Static exchange evaluation plays out every capture on one square, each side always taking back
+// with its least valuable attacker and free to stop whenever going on would lose more. Pieces
+// lined up behind a capturer on the same ray (a rook behind a rook, a queen behind a bishop) join
+// in once the capturer has moved off. Pins and checks are not considered.
+
+/// Counts for more than any exchange, so the king only captures last.
+const KING_VALUE: i32 = 10_000;
+
+/// Material the move wins once the exchange on its target square is played out; negative if
+/// the capturer is lost for less.
+pub fn see(board: &Board, m: &Move) -> i32 {
+    let spaces = board.get_spaces();
+    let captured = match spaces[m.to] {
+        Piece::Piece(p) => value(p.piece),
+        // Only en passant captures onto an empty square.
+        Piece::None if is_pawn(&spaces[m.from]) && m.from % ROW_LEN != m.to % ROW_LEN => value(PieceType::Pawn),
+        Piece::None => 0,
+    };
+    let Some(player) = spaces[m.from].extract_player() else { return 0; };
+    exchange(spaces, m.to, m.from, captured, player)
+}
+
+/// Material the player wins by starting the exchange on `target` with their least valuable
+/// attacker, or 0 if they had better not.
+pub fn see_square(board: &Board, target: usize, player: Player) -> i32 {
+    let spaces = board.get_spaces();
+    let Piece::Piece(victim) = spaces[target] else { return 0; };
+    let first = attackers(&Snapshot::from_spaces(spaces), target, player).into_iter()
+        .min_by_key(|&square| value(spaces[square].kind()));
+    match first {
+        Some(from) => exchange(spaces, target, from, value(victim.piece), player).max(0),
+        None => 0,
+    }
+}
+
+/// The squares of pieces the opponent wins material from by capturing.
+pub fn hanging_squares(board: &Board) -> [bool; BOARD_LEN] {
+    let mut hanging = [false; BOARD_LEN];
+    for (square, piece) in board.get_spaces().iter().enumerate() {
+        let Piece::Piece(p) = piece else { continue; };
+        if matches!(p.piece, PieceType::King) { continue; }
+        hanging[square] = see_square(board, square, p.player.opponent()) > 0;
+    }
+    hanging
+}
+
+/// Plays out the exchange started by the piece on `from` taking `captured` worth on `target`.
+fn exchange(mut spaces: [Piece; BOARD_LEN], target: usize, from: usize, captured: i32, player: Player) -> i32 {
+    let snap = Snapshot::from_spaces(spaces);
+    let mut sides = [attackers(&snap, target, Player::White), attackers(&snap, target, Player::Black)];
+
+    // gains[n] is what the side making capture n has won if the exchange stops after it.
+    let mut gains = vec![captured];
+    let mut on_target = value(spaces[from].kind());
+    let mut capturer = from;
+    let mut side = player;
+
+    loop {
+        remove(&mut spaces, &mut sides, target, capturer);
+        side = side.opponent();
+
+        let mine = &sides[side.index()];
+        let Some(&next) = mine.iter().min_by_key(|&&square| value(spaces[square].kind())) else { break; };
+        // The king can't take back into an attack.
+        if matches!(spaces[next].kind(), PieceType::King) && !sides[side.opponent().index()].is_empty() { break; }
+
+        gains.push(on_target - gains[gains.len() - 1]);
+        on_target = value(spaces[next].kind());
+        capturer = next;
+    }
+
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap_or(0);
+        let previous = gains.len() - 1;
+        gains[previous] = -(-gains[previous]).max(last);
+    }
+    gains[0]
+}
+
+/// Takes the capturer off the board and adds whatever attacker it was screening on the same ray.
+fn remove(spaces: &mut [Piece; BOARD_LEN], sides: &mut [Vec<usize>; 2], target: usize, capturer: usize) {
+    for side in sides.iter_mut() {
+        side.retain(|&square| square != capturer);
+    }
+    spaces[capturer] = Piece::None;
+
+    if let Some(behind) = behind(spaces, target, capturer) {
+        if let Some(owner) = spaces[behind].extract_player() {
+            sides[owner.index()].push(behind);
+        }
+    }
+}
+
+fn value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::King => KING_VALUE,
+        _ => piece_value(piece),
+    }
+}
+
+fn is_pawn(piece: &Piece) -> bool {
+    matches!(piece.extract_value(), Some(PieceType::Pawn))
+}
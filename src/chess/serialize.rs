@@ -0,0 +1,104 @@
+//! Serde support, behind the `serde` feature.
+//!
+//! Pieces, players and castles use the derived forms. A `Move` is a UCI string (`"e2e4"`,
+//! `"e7e8q"`, `"e1g1"` for castling). A `Board` is written as its current FEN, its starting
+//! FEN and its moves, so the history survives; it can be read back from that form or from a bare
+//! FEN string. Use `#[serde(with = "chess::chess::serialize::fen")]` to store a board as a FEN only.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use super::{
+    board::Board,
+    interpret::parse_uci,
+    san::to_uci,
+    utils::Move,
+};
+
+impl Serialize for Move {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.castle {
+            // A castle made with `Move::castle` has no squares of its own; these are never recorded in a history.
+            Some(_) if self.from == self.to => Err(serde::ser::Error::custom("castling move has no squares; play it on a board first")),
+            _ => serializer.serialize_str(&to_uci(self)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Move {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Move::from_uci(&text).map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+struct BoardOut<'a> {
+    fen: String,
+    start: &'a str,
+    moves: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum BoardIn {
+    Fen(String),
+    Game {
+        fen: Option<String>,
+        start: Option<String>,
+        #[serde(default)]
+        moves: Vec<String>,
+    },
+}
+
+impl Serialize for Board {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BoardOut {
+            fen: self.to_fen(),
+            start: self.get_start_fen(),
+            moves: self.get_history().iter().map(to_uci).collect(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Board {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match BoardIn::deserialize(deserializer)? {
+            BoardIn::Fen(fen) => Board::from_fen(&fen).map_err(de::Error::custom),
+            BoardIn::Game { fen, start, moves } => {
+                let mut board = match (&start, &fen) {
+                    (Some(start), _) => Board::from_fen(start),
+                    (None, Some(fen)) if moves.is_empty() => Board::from_fen(fen),
+                    (None, _) => Ok(Board::new()),
+                }.map_err(de::Error::custom)?;
+
+                for text in &moves {
+                    let m = parse_uci(text, &board).map_err(de::Error::custom)?;
+                    board.play(m).map_err(de::Error::custom)?;
+                    board.next_turn();
+                }
+
+                if let Some(fen) = fen {
+                    if board.to_fen() != fen {
+                        return Err(de::Error::custom(format!("the moves lead to {}, not {}", board.to_fen(), fen)));
+                    }
+                }
+                Ok(board)
+            },
+        }
+    }
+}
+
+/// Stores a board as a bare FEN string: `#[serde(with = "chess::chess::serialize::fen")]`.
+/// The move history is not kept.
+pub mod fen {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use crate::chess::board::Board;
+
+    pub fn serialize<S: Serializer>(board: &Board, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&board.to_fen())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Board, D::Error> {
+        let fen = String::deserialize(deserializer)?;
+        Board::from_fen(&fen).map_err(de::Error::custom)
+    }
+}
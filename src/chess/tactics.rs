@@ -0,0 +1,183 @@
+use std::mem;
+use super::{
+    board::{Board, BOARD_LEN, ROW_LEN},
+    checker::{attackers, Snapshot},
+    eval::piece_value,
+    movement::{index_in_raycast, raycast, Direction},
+    san::square_name,
+    see::see_square,
+    utils::{Move, Piece, PieceType, Player},
+};
+
+/// A tactical pattern on the board. Squares are board indices.
+pub enum Motif {
+    /// The piece on `pinned` can't leave the line without exposing `behind` to `attacker`.
+    Pin { attacker: usize, pinned: usize, behind: usize },
+    /// The piece on `front` is attacked and, once it steps aside, `behind` is taken.
+    Skewer { attacker: usize, front: usize, behind: usize },
+    /// One piece attacking two or more targets worth taking.
+    Fork { attacker: usize, targets: Vec<usize> },
+    /// Moving `mover` off the line opens an attack by `slider` on `target`.
+    DiscoveredAttack { slider: usize, mover: usize, target: usize },
+    /// A piece the opponent wins material from by capturing, once the exchange is played out.
+    Hanging { square: usize },
+    /// A king on its first rank with no way off it and no rook or queen guarding the rank.
+    BackRank { king: usize },
+}
+
+/// A motif and the side that can make use of it.
+pub struct Finding {
+    pub player: Player,
+    pub motif: Motif,
+}
+
+impl Finding {
+    /// One line for the position, e.g. "The knight on c6 is pinned to the king on e8 by the bishop on b5".
+    pub fn describe(&self, board: &Board) -> String {
+        let spaces = board.get_spaces();
+        let at = |square: usize| format!("the {} on {}", piece_name(&spaces[square]), square_name(square));
+        let mut text = match &self.motif {
+            Motif::Pin { attacker, pinned, behind } => format!("{} is pinned to {} by {}", at(*pinned), at(*behind), at(*attacker)),
+            Motif::Skewer { attacker, front, behind } => format!("{} skewers {} and {}", at(*attacker), at(*front), at(*behind)),
+            Motif::Fork { attacker, targets } => {
+                let mut targets: Vec<_> = targets.iter().map(|&t| at(t)).collect();
+                let last = targets.pop().unwrap_or_default();
+                format!("{} forks {} and {}", at(*attacker), targets.join(", "), last)
+            },
+            Motif::DiscoveredAttack { slider, mover, target } => format!("moving {} uncovers an attack by {} on {}", at(*mover), at(*slider), at(*target)),
+            Motif::Hanging { square } => format!("{} is hanging", at(*square)),
+            Motif::BackRank { king } => format!("{} is weak on the back rank", at(*king)),
+        };
+        text[..1].make_ascii_uppercase();
+        text
+    }
+}
+
+/// Every motif in the position, for both sides, whoever is to move.
+pub fn find(board: &Board) -> Vec<Finding> {
+    let spaces = board.get_spaces();
+    let snap = Snapshot::new(board);
+    let mut found = Vec::<Finding>::new();
+
+    for player in [Player::White, Player::Black] {
+        let opponent = player.opponent();
+        let undefended = |square: usize| attackers(&snap, square, opponent).is_empty();
+
+        for (from, piece) in spaces.iter().enumerate() {
+            let Piece::Piece(p) = piece else { continue; };
+            if !is(p.player, player) { continue; }
+
+            for direction in slider_directions(p.piece) {
+                let Some(first) = raycast(&spaces, from, direction) else { continue; };
+                let Some(second) = raycast(&spaces, first, direction) else { continue; };
+                let (Some(first_player), Some(second_player)) = (spaces[first].extract_player(), spaces[second].extract_player()) else { continue; };
+                let (first_piece, second_piece) = (spaces[first].kind(), spaces[second].kind());
+                if !is(second_player, opponent) { continue; }
+
+                if is(first_player, opponent) {
+                    if matches!(second_piece, PieceType::King) || value(first_piece) < value(second_piece) {
+                        found.push(Finding { player, motif: Motif::Pin { attacker: from, pinned: first, behind: second } });
+                    } else if (matches!(first_piece, PieceType::King) || value(first_piece) > value(second_piece))
+                        && !matches!(second_piece, PieceType::Pawn)
+                        && (undefended(second) || value(second_piece) >= value(p.piece)) {
+                        found.push(Finding { player, motif: Motif::Skewer { attacker: from, front: first, behind: second } });
+                    }
+                } else if (matches!(second_piece, PieceType::King) || undefended(second) || value(second_piece) > value(p.piece))
+                    && leaves_line(&spaces, from, direction, first, player) {
+                    found.push(Finding { player, motif: Motif::DiscoveredAttack { slider: from, mover: first, target: second } });
+                }
+            }
+
+            let targets: Vec<usize> = attacked_by(&spaces, from, player).into_iter()
+                .filter(|&target| {
+                    let piece = spaces[target].kind();
+                    matches!(piece, PieceType::King) || value(piece) > value(p.piece) || undefended(target)
+                })
+                .collect();
+            if targets.len() >= 2 {
+                found.push(Finding { player, motif: Motif::Fork { attacker: from, targets } });
+            }
+        }
+
+        for (square, piece) in spaces.iter().enumerate() {
+            let Piece::Piece(p) = piece else { continue; };
+            if !is(p.player, opponent) || matches!(p.piece, PieceType::King) { continue; }
+            if see_square(board, square, player) > 0 {
+                found.push(Finding { player, motif: Motif::Hanging { square } });
+            }
+        }
+
+        if let Some(king) = back_rank_weakness(&spaces, &snap, opponent) {
+            found.push(Finding { player, motif: Motif::BackRank { king } });
+        }
+    }
+
+    found
+}
+
+/// The directions a piece slides in; none for pieces that don't slide.
+fn slider_directions(piece: PieceType) -> Vec<Direction> {
+    match piece {
+        PieceType::Rook => Direction::ORTHOGONAL.to_vec(),
+        PieceType::Bishop => Direction::DIAGONAL.to_vec(),
+        PieceType::Queen => Direction::ORTHOGONAL.into_iter().chain(Direction::DIAGONAL).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The opponent's pieces that the piece on `from` attacks.
+fn attacked_by(spaces: &[Piece; BOARD_LEN], from: usize, player: Player) -> Vec<usize> {
+    (0..BOARD_LEN)
+        .filter(|&to| matches!(spaces[to].extract_player(), Some(owner) if is(owner, player.opponent())))
+        .filter(|&to| Move::new(from, to).is_valid_move(spaces, player).is_ok())
+        .collect()
+}
+
+/// Whether the piece on `mover` has a move that takes it off the line from `slider`.
+fn leaves_line(spaces: &[Piece; BOARD_LEN], slider: usize, direction: Direction, mover: usize, player: Player) -> bool {
+    (0..BOARD_LEN).any(|to| !index_in_raycast(slider, direction, to) && Move::new(mover, to).is_valid_move(spaces, player).is_ok())
+}
+
+/// The player's king, if it stands on its first rank boxed in by its own pieces or attacked squares
+/// while the opponent has a rook or queen and the player has none on that rank.
+fn back_rank_weakness(spaces: &[Piece; BOARD_LEN], snap: &Snapshot, player: Player) -> Option<usize> {
+    let home_row = match player { Player::White => BOARD_LEN / ROW_LEN - 1, Player::Black => 0 };
+    let owned = |square: usize, owner: Player| matches!(spaces[square].extract_player(), Some(p) if is(p, owner));
+    let heavy = |square: usize| matches!(spaces[square].extract_value(), Some(PieceType::Rook | PieceType::Queen));
+
+    let king = (0..BOARD_LEN).find(|&i| owned(i, player) && matches!(spaces[i].extract_value(), Some(PieceType::King)))?;
+    if king / ROW_LEN != home_row { return None; }
+    if !(0..BOARD_LEN).any(|i| owned(i, player.opponent()) && heavy(i)) { return None; }
+    if (home_row * ROW_LEN..(home_row + 1) * ROW_LEN).any(|i| owned(i, player) && heavy(i)) { return None; }
+
+    let front_row = match player { Player::White => home_row - 1, Player::Black => home_row + 1 };
+    let file = king % ROW_LEN;
+    let boxed_in = (file.saturating_sub(1)..=(file + 1).min(ROW_LEN - 1))
+        .map(|f| front_row * ROW_LEN + f)
+        .all(|square| owned(square, player) || !attackers(snap, square, player.opponent()).is_empty());
+    boxed_in.then_some(king)
+}
+
+/// Material value, with the king above everything else.
+fn value(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::King => i32::MAX,
+        _ => piece_value(piece),
+    }
+}
+
+fn piece_name(piece: &Piece) -> &'static str {
+    match piece.extract_value() {
+        Some(PieceType::Pawn) => "pawn",
+        Some(PieceType::Knight) => "knight",
+        Some(PieceType::Bishop) => "bishop",
+        Some(PieceType::Rook) => "rook",
+        Some(PieceType::Queen) => "queen",
+        Some(PieceType::King) => "king",
+        None => "square",
+    }
+}
+
+fn is(a: Player, b: Player) -> bool {
+    mem::discriminant(&a) == mem::discriminant(&b)
+}
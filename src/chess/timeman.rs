@@ -0,0 +1,230 @@
+use std::time::{Duration, Instant};
+use super::utils::Move;
+
+// Time management for games on a clock. From the time left, the increment and the moves to the next
+// time control, a move gets two limits:
+//
+//   soft  the time it should normally take; no new depth is started once half of it has gone,
+//         since the next depth would take several times longer than the last one
+//   hard  the time it may never take; a depth still running then is abandoned, and the move
+//         comes from the last finished depth
+//
+// The soft limit stretches, up to the hard one, when the search looks unsure: the best move changed
+// at the last depth, or the score dropped. It shrinks back while the best move holds.
+//
+// Every decision takes the time elapsed as an argument, so a clock can be simulated.
+
+/// Kept back for talking to the GUI and the operating system, so the flag doesn't fall.
+const MOVE_OVERHEAD: Duration = Duration::from_millis(30);
+/// Moves the remaining time is spread over when the time control doesn't say.
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+/// Never planning for more moves than this keeps a long time control from starving each move.
+const MAX_MOVES_TO_GO: u32 = 50;
+/// How many soft limits the hard one may be.
+const HARD_FACTOR: u32 = 4;
+/// The hard limit as a share of the time left, in percent.
+const HARD_SHARE: u32 = 80;
+/// A drop of this many centipawns from one depth to the next counts as the score falling.
+const SCORE_DROP: i32 = 30;
+const MAX_EXTENSION: f64 = 3.0;
+
+/// The state of the side to move's clock.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeControl {
+    pub remaining: Duration,
+    /// Added after every move.
+    pub increment: Duration,
+    /// Moves until more time is added, if the time control has such a point.
+    pub moves_to_go: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TimeManager {
+    started: Instant,
+    soft: Duration,
+    hard: Duration,
+    /// What the soft limit is multiplied by; grows when the search is unsure.
+    extension: f64,
+    last_best: Option<Move>,
+    last_score: Option<i32>,
+    /// A set time for the move rather than a clock to manage: it is all spent.
+    fixed: bool,
+}
+
+impl TimeManager {
+    /// Budgets a move on the given clock, starting now.
+    pub fn new(control: &TimeControl) -> Self {
+        let (soft, hard) = limits(control);
+        TimeManager::with_limits(soft, hard)
+    }
+
+    /// A fixed time for the move, as `go movetime`: thinks up to it and never beyond.
+    pub fn fixed(time: Duration) -> Self {
+        let time = time.saturating_sub(MOVE_OVERHEAD).max(Duration::from_millis(1));
+        TimeManager { fixed: true, ..TimeManager::with_limits(time, time) }
+    }
+
+    pub fn with_limits(soft: Duration, hard: Duration) -> Self {
+        TimeManager { started: Instant::now(), soft, hard: hard.max(soft), extension: 1.0, last_best: None, last_score: None, fixed: false }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// The soft limit, stretched by how unsure the search has been.
+    pub fn soft_limit(&self) -> Duration {
+        self.soft.mul_f64(self.extension).min(self.hard)
+    }
+
+    pub fn hard_limit(&self) -> Duration {
+        self.hard
+    }
+
+    /// When the search must be over.
+    pub fn deadline(&self) -> Instant {
+        self.started + self.hard
+    }
+
+    /// Called when a depth finishes `elapsed` into the move with its best move and score; says
+    /// whether there is time to start the next one.
+    pub fn next_depth(&mut self, elapsed: Duration, best_move: Option<Move>, score: i32) -> bool {
+        if let Some(last) = self.last_best {
+            if best_move.is_some_and(|m| m != last) {
+                self.extension *= 1.5;
+            } else {
+                self.extension *= 0.9;
+            }
+        }
+        if self.last_score.is_some_and(|last| score <= last - SCORE_DROP) {
+            self.extension *= 1.3;
+        }
+        self.extension = self.extension.clamp(1.0, MAX_EXTENSION);
+        self.last_best = best_move.or(self.last_best);
+        self.last_score = Some(score);
+
+        if self.fixed {
+            return elapsed < self.hard;
+        }
+        elapsed < self.soft_limit() / 2 && elapsed < self.hard
+    }
+}
+
+/// The soft and hard limits for a move on this clock.
+pub fn limits(control: &TimeControl) -> (Duration, Duration) {
+    let available = control.remaining.saturating_sub(MOVE_OVERHEAD);
+    let moves = control.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).clamp(1, MAX_MOVES_TO_GO);
+    // The increment comes back after the move, so most of it can be spent now.
+    let share = available / moves + control.increment * 3 / 4;
+
+    let hard = (share * HARD_FACTOR).min(available * HARD_SHARE / 100);
+    let soft = share.min(hard);
+    (soft, hard)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn control(remaining: u64, increment: u64, moves_to_go: Option<u32>) -> TimeControl {
+        TimeControl { remaining: ms(remaining), increment: ms(increment), moves_to_go }
+    }
+
+    #[test]
+    fn allocation() {
+        // A minute, no increment: the time left is spread over 30 moves.
+        let (soft, hard) = limits(&control(60_000, 0, None));
+        assert_eq!((soft, hard), (ms(59_970) / 30, ms(59_970) / 30 * 4));
+
+        // The increment adds three quarters of itself.
+        let (soft, hard) = limits(&control(60_000, 1000, None));
+        assert_eq!(soft, ms(59_970) / 30 + ms(750));
+        assert_eq!(hard, soft * 4);
+
+        // The last move before the time control may use most of the time, but never all of it.
+        let (soft, hard) = limits(&control(5000, 0, Some(1)));
+        assert_eq!(soft, hard);
+        assert_eq!(hard, ms(4970) * 80 / 100);
+
+        // A long way to the time control still keeps something for this move.
+        let (soft, _) = limits(&control(60_000, 0, Some(200)));
+        assert_eq!(soft, ms(59_970) / 50);
+    }
+
+    #[test]
+    fn never_more_than_the_clock() {
+        for remaining in [0, 10, 30, 31, 100, 1000, 10_000, 600_000] {
+            for increment in [0, 100, 2000, 30_000] {
+                for moves_to_go in [None, Some(1), Some(5), Some(40)] {
+                    let (soft, hard) = limits(&control(remaining, increment, moves_to_go));
+                    assert!(soft <= hard);
+                    assert!(hard <= ms(remaining).saturating_sub(MOVE_OVERHEAD), "{} {} {:?}", remaining, increment, moves_to_go);
+                }
+            }
+        }
+
+        // A game where every move takes the whole hard limit still never runs the clock out.
+        let mut remaining = ms(10_000);
+        for _ in 0..200 {
+            let (_, hard) = limits(&TimeControl { remaining, increment: ms(100), moves_to_go: None });
+            assert!(hard + MOVE_OVERHEAD <= remaining || hard.is_zero());
+            remaining = remaining - hard + ms(100);
+        }
+        assert!(remaining > MOVE_OVERHEAD);
+    }
+
+    #[test]
+    fn unstable_best_move_or_falling_score_extends() {
+        let (a, b) = (Some(Move::new(52, 36)), Some(Move::new(51, 35)));
+        let mut time = TimeManager::with_limits(ms(1000), ms(4000));
+
+        assert!(time.next_depth(ms(10), a, 20));
+        assert!(time.next_depth(ms(50), a, 20));
+        assert_eq!(time.soft_limit(), ms(1000));
+
+        // The best move changes.
+        assert!(time.next_depth(ms(120), b, 20));
+        assert_eq!(time.soft_limit(), ms(1500));
+
+        // The score drops with it holding: the drop outweighs the steadier move.
+        assert!(time.next_depth(ms(300), b, -40));
+        assert!(time.soft_limit() > ms(1500));
+
+        // Holding steady, the extension wears off again, and past half of it no depth starts.
+        let before = time.soft_limit();
+        assert!(time.next_depth(ms(700), b, -40));
+        assert!(time.soft_limit() < before);
+        assert!(!time.next_depth(ms(900), b, -40));
+    }
+
+    #[test]
+    fn extension_stays_under_the_hard_limit() {
+        let (a, b) = (Some(Move::new(52, 36)), Some(Move::new(51, 35)));
+        let mut time = TimeManager::with_limits(ms(1000), ms(2000));
+        for i in 0..20 {
+            time.next_depth(ms(0), if i % 2 == 0 { a } else { b }, -100 * i);
+        }
+        assert_eq!(time.soft_limit(), ms(2000));
+    }
+
+    #[test]
+    fn stops_at_half_the_soft_limit() {
+        let mut time = TimeManager::with_limits(ms(1000), ms(4000));
+        assert!(time.next_depth(ms(499), None, 0));
+        let mut time = TimeManager::with_limits(ms(1000), ms(4000));
+        assert!(!time.next_depth(ms(500), None, 0));
+    }
+
+    #[test]
+    fn fixed_time_is_all_spent() {
+        let mut time = TimeManager::fixed(ms(500));
+        assert_eq!(time.hard_limit(), ms(470));
+        assert!(time.next_depth(ms(400), None, 0));
+        assert!(!time.next_depth(ms(480), None, 0));
+        assert_eq!(TimeManager::fixed(ms(10)).hard_limit(), ms(1));
+    }
+}
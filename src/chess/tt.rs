@@ -0,0 +1,193 @@
+use std::{
+    mem,
+    sync::{atomic::{AtomicU8, Ordering}, Mutex, MutexGuard, PoisonError},
+};
+use super::{engine::MATE, utils::Move};
+
+// The transposition table remembers what the search found out about positions it has seen, keyed
+// by the position's Zobrist hash. Entries live in buckets of four; a position can only go in the
+// bucket its key picks. When the bucket is full, the entry searched least deep, counting entries
+// left over from earlier searches as shallower still, makes room.
+//
+// Every bucket has its own lock, so the threads of a parallel search can share one table.
+//
+// Mate scores count plies from the root, so they are stored counting from the position itself and
+// turned back on the way out; the same mate is found at a different ply in another line.
+
+/// Size used when none is asked for.
+pub const DEFAULT_HASH_MB: usize = 16;
+pub const MAX_HASH_MB: usize = 4096;
+const BUCKET_SIZE: usize = 4;
+/// How much shallower an entry from an earlier search counts for when choosing one to replace.
+const AGE_PENALTY: i32 = 4;
+
+/// What the stored score says about the position's real score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is exact.
+    Exact,
+    /// The real score is at least this; the search stopped at a move good enough to cut off.
+    Lower,
+    /// The real score is at most this; no move reached alpha.
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Entry {
+    pub key: u64,
+    pub depth: u32,
+    pub bound: Bound,
+    /// Score for the side to move, with mates counted from this position.
+    score: i32,
+    pub best_move: Option<Move>,
+    generation: u8,
+}
+
+impl Entry {
+    /// The stored score with mates counted from the root again, `ply` plies up.
+    pub fn score(&self, ply: i32) -> i32 {
+        if self.score > MATE - 1000 {
+            self.score - ply
+        } else if self.score < -(MATE - 1000) {
+            self.score + ply
+        } else {
+            self.score
+        }
+    }
+}
+
+type Bucket = [Option<Entry>; BUCKET_SIZE];
+
+pub struct TranspositionTable {
+    buckets: Vec<Mutex<Bucket>>,
+    /// Counts the searches, so entries from old ones are replaced first.
+    generation: AtomicU8,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(DEFAULT_HASH_MB)
+    }
+}
+
+impl TranspositionTable {
+    /// A table taking about `megabytes` of memory, at least one bucket.
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes.min(MAX_HASH_MB) * 1024 * 1024 / mem::size_of::<Mutex<Bucket>>()).max(1);
+        let buckets = (0..count).map(|_| Mutex::new([None; BUCKET_SIZE])).collect();
+        TranspositionTable { buckets, generation: AtomicU8::new(0) }
+    }
+
+    /// Forgets everything, e.g. for a new game.
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            *bucket.get_mut().unwrap_or_else(PoisonError::into_inner) = [None; BUCKET_SIZE];
+        }
+        *self.generation.get_mut() = 0;
+    }
+
+    /// Marks the start of a new search; what earlier searches stored is kept but ages.
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        self.bucket(key).iter().flatten().find(|entry| entry.key == key).copied()
+    }
+
+    /// Stores a result found `ply` plies from the root.
+    pub fn store(&self, key: u64, depth: u32, bound: Bound, score: i32, best_move: Option<Move>, ply: i32) {
+        let score = if score > MATE - 1000 {
+            score + ply
+        } else if score < -(MATE - 1000) {
+            score - ply
+        } else {
+            score
+        };
+        let generation = self.generation.load(Ordering::Relaxed);
+        let mut bucket = self.bucket(key);
+
+        let same = bucket.iter().position(|entry| matches!(entry, Some(e) if e.key == key));
+        if let Some(old) = same.and_then(|slot| bucket[slot]) {
+            // A shallower result for the same position only replaces a deeper one from an old
+            // search, or when it is exact and the old one isn't.
+            if depth < old.depth && old.generation == generation && (bound != Bound::Exact || old.bound == Bound::Exact) {
+                return;
+            }
+        }
+        let slot = same.unwrap_or_else(|| (0..BUCKET_SIZE).min_by_key(|&slot| match bucket[slot] {
+            None => i32::MIN,
+            Some(e) => e.depth as i32 - if e.generation == generation { 0 } else { AGE_PENALTY },
+        }).unwrap_or(0));
+
+        // Keep the old move when the new result has none, e.g. when no move reached alpha.
+        let best_move = best_move.or(bucket[slot].filter(|e| e.key == key).and_then(|e| e.best_move));
+        bucket[slot] = Some(Entry { key, depth, bound, score, best_move, generation });
+    }
+
+    /// How full the table is, in thousandths, from a sample of its buckets.
+    pub fn hashfull(&self) -> usize {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let sample = self.buckets.iter().take(1000);
+        let (used, total) = sample.fold((0, 0), |(used, total), bucket| {
+            let bucket = bucket.lock().unwrap_or_else(PoisonError::into_inner);
+            (used + bucket.iter().flatten().filter(|e| e.generation == generation).count(), total + BUCKET_SIZE)
+        });
+        used * 1000 / total.max(1)
+    }
+
+    /// The bucket for a key, locked.
+    fn bucket(&self, key: u64) -> MutexGuard<'_, Bucket> {
+        let index = (key % self.buckets.len() as u64) as usize;
+        self.buckets[index].lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mates_are_stored_from_the_position_and_read_back_from_the_root() {
+        let tt = TranspositionTable::new(1);
+        // Mate in 5 plies from the root, found 3 plies down: 2 plies from the position itself.
+        tt.store(1, 4, Bound::Exact, MATE - 5, None, 3);
+        let entry = tt.probe(1).unwrap();
+        assert_eq!(entry.score(3), MATE - 5);
+        assert_eq!(entry.score(1), MATE - 3);
+
+        tt.store(2, 4, Bound::Exact, -MATE + 4, None, 2);
+        assert_eq!(tt.probe(2).unwrap().score(5), -MATE + 7);
+
+        tt.store(3, 4, Bound::Exact, 250, None, 6);
+        assert_eq!(tt.probe(3).unwrap().score(1), 250);
+    }
+
+    #[test]
+    fn shallower_results_replace_deeper_ones_only_when_they_should() {
+        let tt = TranspositionTable::new(1);
+        let m = Move::new(52, 36);
+
+        tt.store(1, 6, Bound::Lower, 100, Some(m), 0);
+        tt.store(1, 3, Bound::Lower, 50, None, 0);
+        let entry = tt.probe(1).unwrap();
+        assert_eq!((entry.depth, entry.score(0)), (6, 100));
+
+        // An exact score is worth more than a deeper bound.
+        tt.store(1, 3, Bound::Exact, 60, None, 0);
+        let entry = tt.probe(1).unwrap();
+        assert_eq!((entry.depth, entry.bound, entry.score(0)), (3, Bound::Exact, 60));
+        assert_eq!(entry.best_move, Some(m));
+
+        // But not more than a deeper exact score.
+        tt.store(1, 8, Bound::Exact, 70, None, 0);
+        tt.store(1, 2, Bound::Exact, 80, None, 0);
+        assert_eq!(tt.probe(1).unwrap().depth, 8);
+
+        // Anything replaces what an earlier search left.
+        tt.new_search();
+        tt.store(1, 2, Bound::Upper, 10, None, 0);
+        let entry = tt.probe(1).unwrap();
+        assert_eq!((entry.depth, entry.bound), (2, Bound::Upper));
+    }
+}
@@ -0,0 +1,349 @@
+use std::{
+    io::{BufRead, Write},
+    sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex, MutexGuard, PoisonError},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+use anyhow::{anyhow, Result};
+use super::{
+    board::Board,
+    engine::{search_lines, Analysis, Limits, MATE, MAX_DEPTH},
+    interpret::parse_uci,
+    san::to_uci,
+    timeman::{TimeControl, TimeManager},
+    tt::{TranspositionTable, DEFAULT_HASH_MB, MAX_HASH_MB},
+    utils::Player,
+};
+
+// The Universal Chess Interface, for chess GUIs. The GUI sends one command per line on standard
+// input and the engine answers on standard output:
+//
+//   uci                                   id name ..., id author ..., option ..., uciok
+//   isready                               readyok
+//   setoption name MultiPV value 3
+//   setoption name Hash value 64          transposition table size in MB
+//   setoption name Clear Hash
+//   setoption name Threads value 4        search threads sharing the table
+//   setoption name Ponder value true      lets the GUI send go ponder
+//   ucinewgame                            also clears the transposition table
+//   position startpos [moves e2e4 e7e5]
+//   position fen <FEN> [moves ...]
+//   go [depth N]                          info ... lines, info hashfull, then bestmove e2e4
+//   go wtime 60000 btime 60000 [winc 1000 binc 1000] [movestogo 20]
+//                                         thinks as long as the clock allows (see timeman.rs)
+//   go movetime 2000                      thinks two seconds
+//   go infinite                           thinks until stop
+//   go ponder [wtime ...]                 thinks on the opponent's time, on the position after
+//                                         the move it expects, until ponderhit or stop
+//   ponderhit                             the opponent played it: goes on as a normal go
+//   stop                                  ends the search; bestmove comes from the last finished depth
+//   quit
+//
+// The search runs on its own thread, so `stop` and `isready` are answered while it thinks. Other
+// commands wait for it to finish, except that `setoption` or `go` during `go infinite` or `go ponder`
+// stop it first, as those would never finish on their own.
+// `bestmove` names the move it expects in reply when it has one, as in `bestmove e2e4 ponder e7e5`,
+// for the GUI to ponder on. On `ponderhit` the ponder search stops without a `bestmove` and a normal
+// one with the `go` command's clocks takes over, finding the ponder search's results in the table.
+//
+// While searching, every finished depth is reported with one line per principal variation, with
+// the nodes of all threads:
+//
+//   info depth 4 multipv 1 score cp 35 nodes 12345 pv e2e4 e7e5 g1f3
+//
+// Unknown commands and `go` parameters the engine doesn't use are ignored, as the protocol asks.
+
+const NAME: &str = "chess";
+const AUTHOR: &str = "the chess authors";
+/// Depth searched when `go` gives neither a depth nor any time.
+const DEFAULT_DEPTH: u32 = 4;
+const MAX_MULTIPV: usize = 32;
+const MAX_THREADS: usize = 64;
+/// How often a finished `go infinite` looks for `stop`.
+const STOP_POLL: Duration = Duration::from_millis(5);
+
+pub struct Uci {
+    board: Board,
+    multipv: usize,
+    threads: usize,
+    hash_mb: usize,
+    tt: Arc<TranspositionTable>,
+    search: Option<Search>,
+}
+
+/// A `go` running on its own thread, so commands such as `stop` are read meanwhile.
+struct Search {
+    stop: Arc<AtomicBool>,
+    /// Searches with no end of their own (`go infinite`, `go ponder`) wait for `stop` before `bestmove`.
+    infinite: bool,
+    /// For `go ponder`, the `go` to start in its place on `ponderhit`.
+    ponder: Option<Vec<String>>,
+    /// Set when the search is replaced, so it ends without a `bestmove`.
+    quiet: Arc<AtomicBool>,
+    handle: JoinHandle<Result<()>>,
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self {
+            board: Board::new(),
+            multipv: 1,
+            threads: 1,
+            hash_mb: DEFAULT_HASH_MB,
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            search: None,
+        }
+    }
+}
+
+impl Uci {
+    /// Answers commands from `input` until it ends or says `quit`. A search still running when the
+    /// input ends is finished first, unless it would only end on `stop`.
+    pub fn run(&mut self, input: impl BufRead, output: impl Write + Send + 'static) -> Result<()> {
+        let output = Arc::new(Mutex::new(output));
+        for line in input.lines() {
+            let line = line?;
+            let args: Vec<&str> = line.split_whitespace().collect();
+            match args.as_slice() {
+                ["quit", ..] => {
+                    return self.finish_search(true);
+                },
+                ["stop", ..] => self.finish_search(true)?,
+                ["uci", ..] => {
+                    let mut output = lock(&output);
+                    writeln!(output, "id name {}", NAME)?;
+                    writeln!(output, "id author {}", AUTHOR)?;
+                    writeln!(output, "option name MultiPV type spin default 1 min 1 max {}", MAX_MULTIPV)?;
+                    writeln!(output, "option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_MB, MAX_HASH_MB)?;
+                    writeln!(output, "option name Clear Hash type button")?;
+                    writeln!(output, "option name Threads type spin default 1 min 1 max {}", MAX_THREADS)?;
+                    writeln!(output, "option name Ponder type check default false")?;
+                    writeln!(output, "uciok")?;
+                },
+                ["isready", ..] => { writeln!(lock(&output), "readyok")?; },
+                ["ucinewgame", ..] => {
+                    self.finish_search(true)?;
+                    self.board = Board::new();
+                    self.clear_hash();
+                },
+                ["setoption", rest @ ..] => {
+                    self.finish_search(self.unbounded())?;
+                    self.set_option(rest);
+                },
+                ["position", rest @ ..] => {
+                    // A position the engine can't read leaves the old one; there is no way to report it.
+                    if let Ok(board) = position(rest) {
+                        self.board = board;
+                    }
+                },
+                ["go", rest @ ..] => {
+                    self.finish_search(self.unbounded())?;
+                    self.go(rest, Arc::clone(&output));
+                },
+                ["ponderhit", ..] => self.ponderhit(Arc::clone(&output))?,
+                _ => { },
+            }
+            lock(&output).flush()?;
+        }
+        self.finish_search(self.unbounded())
+    }
+
+    /// Whether the running search only ends on `stop`. A command that must wait for the search
+    /// stops such a one, with its `bestmove`, rather than waiting forever.
+    fn unbounded(&self) -> bool {
+        self.search.as_ref().is_some_and(|search| search.infinite)
+    }
+
+    fn set_option(&mut self, args: &[&str]) {
+        let (name, value) = option_name_value(args);
+        match name.to_lowercase().as_str() {
+            "multipv" => {
+                if let Ok(n) = value.parse::<usize>() {
+                    self.multipv = n.clamp(1, MAX_MULTIPV);
+                }
+            },
+            "hash" => {
+                if let Ok(megabytes) = value.parse::<usize>() {
+                    self.hash_mb = megabytes.clamp(1, MAX_HASH_MB);
+                    self.tt = Arc::new(TranspositionTable::new(self.hash_mb));
+                }
+            },
+            "clear hash" => self.clear_hash(),
+            "threads" => {
+                if let Ok(n) = value.parse::<usize>() {
+                    self.threads = n.clamp(1, MAX_THREADS);
+                }
+            },
+            _ => { },
+        }
+    }
+
+    /// Only called with no search running, so nothing else holds the table.
+    fn clear_hash(&mut self) {
+        match Arc::get_mut(&mut self.tt) {
+            Some(tt) => tt.clear(),
+            None => self.tt = Arc::new(TranspositionTable::new(self.hash_mb)),
+        }
+    }
+
+    /// Starts searching on its own thread; it writes `info` lines as it goes and `bestmove` when done.
+    fn go(&mut self, args: &[&str], output: Arc<Mutex<impl Write + Send + 'static>>) {
+        let go = GoOptions::parse(args, &self.board);
+        let stop = Arc::new(AtomicBool::new(false));
+        let quiet = Arc::new(AtomicBool::new(false));
+        let infinite = go.infinite;
+        let ponder = go.ponder.then(|| args.iter().filter(|a| **a != "ponder").map(|a| a.to_string()).collect());
+        let (board, multipv, threads, tt) = (self.board.clone(), self.multipv, self.threads, Arc::clone(&self.tt));
+        let (thread_stop, thread_quiet) = (Arc::clone(&stop), Arc::clone(&quiet));
+
+        let handle = thread::spawn(move || -> Result<()> {
+            let limits = Limits { depth: go.depth, time: go.time, stop: Some(&thread_stop) };
+            let mut failed = None;
+            let analysis = search_lines(&board, limits, multipv, threads, &tt, |analysis| {
+                let mut output = lock(&output);
+                if let Err(error) = write_info(&mut *output, analysis).and_then(|_| output.flush()) {
+                    failed.get_or_insert(error);
+                }
+            });
+            if let Some(error) = failed {
+                return Err(error.into());
+            }
+            while go.infinite && !thread_stop.load(Ordering::Relaxed) {
+                thread::sleep(STOP_POLL);
+            }
+            if thread_quiet.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            let mut output = lock(&output);
+            writeln!(output, "info hashfull {}", tt.hashfull())?;
+            let best = analysis.lines.first();
+            match (best.and_then(|line| line.best_move), best.and_then(|line| line.pv.get(1))) {
+                (Some(m), Some(reply)) => writeln!(output, "bestmove {} ponder {}", to_uci(&m), to_uci(reply))?,
+                (Some(m), None) => writeln!(output, "bestmove {}", to_uci(&m))?,
+                (None, _) => writeln!(output, "bestmove 0000")?,
+            }
+            output.flush()?;
+            Ok(())
+        });
+        self.search = Some(Search { stop, infinite, ponder, quiet, handle });
+    }
+
+    /// The opponent played the move pondered on: the ponder search gives way to a normal one on
+    /// the same position, on the clock from now, which finds what it did in the table.
+    fn ponderhit(&mut self, output: Arc<Mutex<impl Write + Send + 'static>>) -> Result<()> {
+        let Some(args) = self.search.as_mut().and_then(|search| search.ponder.take()) else { return Ok(()); };
+        if let Some(search) = &self.search {
+            search.quiet.store(true, Ordering::Relaxed);
+        }
+        self.finish_search(true)?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        self.go(&args, output);
+        Ok(())
+    }
+
+    /// Waits for the running search, if any, to write its `bestmove`; with `stop`, tells it to end first.
+    fn finish_search(&mut self, stop: bool) -> Result<()> {
+        let Some(search) = self.search.take() else { return Ok(()); };
+        if stop {
+            search.stop.store(true, Ordering::Relaxed);
+        }
+        search.handle.join().map_err(|_| anyhow!("the search thread panicked"))?
+    }
+}
+
+/// What a `go` command asks for.
+struct GoOptions {
+    depth: u32,
+    time: Option<TimeManager>,
+    /// Searches until `stop`, whatever the limits.
+    infinite: bool,
+    ponder: bool,
+}
+
+impl GoOptions {
+    /// Reads `depth`, `movetime`, `infinite`, `ponder` and the clocks `wtime btime winc binc
+    /// movestogo`, of which only the side to move's count. Pondering ignores the clock; it applies
+    /// from `ponderhit`.
+    fn parse(args: &[&str], board: &Board) -> Self {
+        let number = |name: &str| args.iter().position(|a| *a == name)
+            .and_then(|i| args.get(i + 1))
+            .and_then(|n| n.parse::<i64>().ok());
+        let millis = |name: &str| number(name).map(|n| Duration::from_millis(n.max(0) as u64));
+        let (time, increment) = match board.get_turn() {
+            Player::White => ("wtime", "winc"),
+            Player::Black => ("btime", "binc"),
+        };
+
+        let ponder = args.contains(&"ponder");
+        let infinite = ponder || args.contains(&"infinite");
+        let time = if infinite {
+            None
+        } else if let Some(movetime) = millis("movetime") {
+            Some(TimeManager::fixed(movetime))
+        } else {
+            millis(time).map(|remaining| TimeManager::new(&TimeControl {
+                remaining,
+                increment: millis(increment).unwrap_or_default(),
+                moves_to_go: number("movestogo").map(|n| n.clamp(1, u32::MAX as i64) as u32),
+            }))
+        };
+        let depth = match number("depth") {
+            Some(depth) => depth.clamp(1, MAX_DEPTH as i64) as u32,
+            None if infinite || time.is_some() => MAX_DEPTH,
+            None => DEFAULT_DEPTH,
+        };
+        GoOptions { depth, time, infinite, ponder }
+    }
+}
+
+/// The output, even if a thread panicked while writing to it.
+fn lock<W>(output: &Mutex<W>) -> MutexGuard<'_, W> {
+    output.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Writes one `info` line per principal variation of a finished depth.
+fn write_info(output: &mut impl Write, analysis: &Analysis) -> std::io::Result<()> {
+    for (i, line) in analysis.lines.iter().enumerate() {
+        let pv: Vec<String> = line.pv.iter().map(to_uci).collect();
+        writeln!(
+            output,
+            "info depth {} multipv {} score {} nodes {} pv {}",
+            analysis.depth, i + 1, uci_score(line.score), analysis.nodes, pv.join(" "),
+        )?;
+    }
+    Ok(())
+}
+
+/// `cp 35`, or `mate 3` / `mate -3` in moves (not plies) for a forced mate.
+pub fn uci_score(score: i32) -> String {
+    if score.abs() > MATE - 1000 {
+        let moves = (MATE - score.abs() + 1) / 2;
+        format!("mate {}", if score > 0 { moves } else { -moves })
+    } else {
+        format!("cp {}", score)
+    }
+}
+
+/// Splits `name <words> value <words>`; option names may have spaces.
+fn option_name_value(args: &[&str]) -> (String, String) {
+    let value_at = args.iter().position(|a| *a == "value").unwrap_or(args.len());
+    let name = args[..value_at].iter().skip_while(|a| **a == "name").copied().collect::<Vec<_>>().join(" ");
+    let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+    (name, value)
+}
+
+/// The board for `startpos|fen <FEN> [moves ...]`.
+fn position(args: &[&str]) -> Result<Board> {
+    let moves_at = args.iter().position(|a| *a == "moves").unwrap_or(args.len());
+    let mut board = match args.first() {
+        Some(&"fen") => Board::from_fen(&args[1..moves_at].join(" "))?,
+        _ => Board::new(),
+    };
+    for text in args.get(moves_at + 1..).unwrap_or_default() {
+        let m = parse_uci(text, &board)?;
+        board.play(m)?;
+        board.next_turn();
+    }
+    Ok(board)
+}
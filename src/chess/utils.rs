@@ -1,14 +1,19 @@
 // Type Definitions
 
+use anyhow::{anyhow, Result};
 use colored::*;
+use super::interpret::parse_square;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
 pub enum Piece {
     None,
     Piece(PlayerPiece),
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PieceType {
     Pawn,
     Rook,
@@ -18,26 +23,31 @@ pub enum PieceType {
     King,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Player {
     White,
     Black,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PlayerPiece {
     pub piece: PieceType,
     pub player: Player,
 }
 
-#[derive(Clone, Copy)]
+/// Serialized as a UCI string such as `e2e4` (see `chess::serialize`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Move {
     pub from: usize,
     pub to: usize,
     pub castle: Option<Castle>,
+    pub promotion: Option<PieceType>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Castle {
     Short,
     Long,
@@ -47,15 +57,76 @@ pub enum Castle {
 
 impl Move {
     pub fn new(from: usize, to: usize) -> Self {
-        Self { from, to, castle: None }
+        Self { from, to, castle: None, promotion: None }
     }
     pub fn castle(castle_long: bool) -> Self {
         let castle = if castle_long { Castle::Long } else { Castle::Short };
-        Self { from: 0, to: 0, castle: Some(castle) }
+        Self { from: 0, to: 0, castle: Some(castle), promotion: None }
+    }
+    pub fn promote(from: usize, to: usize, piece: PieceType) -> Self {
+        Self { from, to, castle: None, promotion: Some(piece) }
+    }
+    /// Reads a move in UCI form (`e2e4`, `e7e8q`) without checking it against a position.
+    /// Castling stays a king move; `Board::play` recognises it.
+    pub fn from_uci(input: &str) -> Result<Self> {
+        if input.len() < 4 || input.len() > 5 || !input.is_ascii() {
+            return Err(anyhow!("Invalid move '{}'!", input));
+        }
+        let from = parse_square(&input[0..2])?;
+        let to = parse_square(&input[2..4])?;
+        let promotion = match input.chars().nth(4) {
+            None => None,
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(_) => { return Err(anyhow!("Invalid promotion in '{}'!", input)); },
+        };
+        Ok(Self { from, to, castle: None, promotion })
+    }
+}
+
+impl PieceType {
+    /// Material value in pawns.
+    pub fn value(&self) -> u32 {
+        match self {
+            PieceType::Pawn => 1,
+            PieceType::Knight => 3,
+            PieceType::Bishop => 3,
+            PieceType::Rook => 5,
+            PieceType::Queen => 9,
+            PieceType::King => 0,
+        }
+    }
+
+    pub fn to_char(&self) -> char {
+        PlayerPiece::new(*self, Player::White).to_char()
+    }
+}
+
+impl Player {
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::White => Player::Black,
+            Player::Black => Player::White,
+        }
+    }
+
+    /// 0 for White and 1 for Black, for tables kept per side.
+    pub fn index(self) -> usize {
+        match self {
+            Player::White => 0,
+            Player::Black => 1,
+        }
     }
 }
 
 impl Piece {
+    /// The type of piece on a square known to be occupied; an empty square reads as a pawn.
+    pub fn kind(&self) -> PieceType {
+        self.extract_value().unwrap_or(PieceType::Pawn)
+    }
+
     pub fn extract_value(&self) -> Option<PieceType> {
         if let Piece::Piece(p) = self {
             return Some(p.piece);
@@ -85,6 +156,34 @@ impl PlayerPiece {
         Self { piece, player }
     }
 
+    pub fn to_char(&self) -> char {
+        match self.piece {
+            PieceType::Pawn => 'P',
+            PieceType::Rook => 'R',
+            PieceType::Knight => 'N',
+            PieceType::Bishop => 'B',
+            PieceType::Queen => 'Q',
+            PieceType::King => 'K',
+        }
+    }
+
+    pub fn to_unicode(&self) -> char {
+        match (self.player, self.piece) {
+            (Player::White, PieceType::Pawn) => '♙',
+            (Player::White, PieceType::Rook) => '♖',
+            (Player::White, PieceType::Knight) => '♘',
+            (Player::White, PieceType::Bishop) => '♗',
+            (Player::White, PieceType::Queen) => '♕',
+            (Player::White, PieceType::King) => '♔',
+            (Player::Black, PieceType::Pawn) => '♟',
+            (Player::Black, PieceType::Rook) => '♜',
+            (Player::Black, PieceType::Knight) => '♞',
+            (Player::Black, PieceType::Bishop) => '♝',
+            (Player::Black, PieceType::Queen) => '♛',
+            (Player::Black, PieceType::King) => '♚',
+        }
+    }
+
     pub fn to_colored_string(&self, is_moved: bool) -> ColoredString {
         #[allow(unused_assignments)]
         let mut output = "".normal();
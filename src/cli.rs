@@ -0,0 +1,666 @@
+use std::{collections::HashMap, fs, path::Path, time::Instant};
+use anyhow::{anyhow, Context, Result};
+use crate::chess::{
+    self,
+    api::{ApiServer, DEFAULT_API_PORT},
+    board::Board,
+    book::Book,
+    correspondence,
+    dtm::{self, Dtm, DtmTable, DtmTables, Ending},
+    eco,
+    broadcast::{self, Broadcaster},
+    engine,
+    eval::evaluate_white,
+    game::{Game, Opponent},
+    movement::{legal_moves, perft},
+    pawns::{pawn_structure, PawnStructure},
+    net,
+    puzzle::{self, History},
+    review::{self, Class},
+    pgn::{parse_pgn, PgnGame},
+    render::{Orientation, RenderOptions},
+    san::{line_to_san, square_name, to_san, to_uci},
+    tactics,
+    tt::TranspositionTable,
+    uci::Uci,
+    utils::Player,
+    LoopState,
+};
+
+pub const USAGE: &str = "\
+Usage: chess [command] [options]
+
+Commands:
+  play [--side white|black] [--depth N] [--fen FEN] [--load FILE] [--broadcast PORT] [--book FILE]
+       [--ponder on|off]  Play in the terminal. With --side you play that side against the engine,
+                          which opens from the Polyglot book if one is given, and thinks on your
+                          time unless --ponder is off.
+                          With --broadcast, spectators can watch on that port.
+  perft <depth> [FEN]     Count the legal move tree, split by the first move.
+  analyze <FEN> [--depth N] [--book FILE] [--multipv N] [--threads N]
+                          Evaluate a position and show the engine's best line (or N best lines)
+                          and any book moves.
+  review <PGN> [--depth N] [--output FILE]
+                          Go over finished games with the engine: mistakes, blunders and accuracy.
+                          With --output, write the games back with comments and NAGs.
+  convert <FILE|FEN> [--to fen|pgn]
+                          Turn a FEN into a PGN, or a PGN file into the FEN of its final position.
+  bench [--depth N]       Search a fixed set of positions and report the speed.
+  host [--port N] [--side white|black] [--name NAME] [--broadcast PORT]
+                          Host a two-player game on the local network.
+  join <ADDRESS[:PORT]> [--name NAME]
+                          Join a game hosted on another computer.
+  watch <ADDRESS[:PORT]>  Follow a broadcast game as a spectator.
+  serve [--port N]        Run the HTTP/JSON API for creating and playing games.
+  correspondence new <FILE> [--white NAME] [--black NAME] [--fen FEN]
+  correspondence show <FILE>
+  correspondence move <FILE> <MOVE> --as <white|black|NAME>
+                          Play a slow game through a shared PGN file, one move at a time.
+  book build <PGN>... --output FILE [--plies N]
+                          Build a Polyglot opening book from PGN files.
+  book show <FILE> [FEN]  List a book's moves for a position.
+  dtm build <DIR> [KQK|KRK|KPK|KBNK]...
+                          Generate distance-to-mate tables for small endings (all four by default).
+  dtm probe <DIR> <FEN>   Look a position up in generated tables.
+  puzzle <CSV> [--id ID] [--theme THEME] [--history FILE]
+                          Solve puzzles from a Lichess-style CSV, rated by how you do.
+  puzzle stats [--history FILE]
+                          Show your puzzle score and rating history.
+  uci                     Talk the Universal Chess Interface on standard input and output, for GUIs.
+  help                    Show this message.
+
+Running `chess <FILE>` resumes a saved game.";
+
+const DEFAULT_DEPTH: u32 = 3;
+
+const BENCH_POSITIONS: [&str; 6] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+    "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+    "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+];
+
+/// Runs the command named by the arguments (without the program name).
+pub fn dispatch(args: &[String]) -> Result<()> {
+    match args.first().map(|a| a.as_str()) {
+        None => play(&[]),
+        Some("play") => play(&args[1..]),
+        Some("perft") => perft_command(&args[1..]),
+        Some("analyze") => analyze(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("bench") => bench(&args[1..]),
+        Some("host") => host(&args[1..]),
+        Some("join") => join(&args[1..]),
+        Some("watch") => watch(&args[1..]),
+        Some("serve") => serve(&args[1..]),
+        Some("correspondence" | "corr") => correspondence_command(&args[1..]),
+        Some("book") => book_command(&args[1..]),
+        Some("dtm") => dtm_command(&args[1..]),
+        Some("puzzle") => puzzle_command(&args[1..]),
+        Some("review") => review_command(&args[1..]),
+        Some("uci") => Uci::default().run(std::io::stdin().lock(), std::io::stdout()),
+        Some("help" | "-h" | "--help") => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        Some(path) if Path::new(path).is_file() => play(&["--load".to_string(), path.to_string()]),
+        Some(other) => Err(anyhow!("Unknown command '{}'.\n\n{}", other, USAGE)),
+    }
+}
+
+/// Splits arguments into positional ones and `--name value` options. Only the names in `known` are accepted.
+fn parse_options(args: &[String], known: &[&str]) -> Result<(Vec<String>, HashMap<String, String>)> {
+    let mut positional = Vec::<String>::new();
+    let mut options = HashMap::<String, String>::new();
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                if !known.contains(&name) {
+                    return Err(anyhow!("Unknown option '--{}'.", name));
+                }
+                let value = it.next().ok_or_else(|| anyhow!("Option '--{}' needs a value.", name))?;
+                options.insert(name.to_string(), value.clone());
+            },
+            None => positional.push(arg.clone()),
+        }
+    }
+
+    Ok((positional, options))
+}
+
+fn depth_option(options: &HashMap<String, String>, default: u32) -> Result<u32> {
+    match options.get("depth") {
+        None => Ok(default),
+        Some(depth) => depth.parse().map_err(|_| anyhow!("The depth must be a number.")),
+    }
+}
+
+fn parse_side(side: &str) -> Result<Player> {
+    match side.to_lowercase().as_str() {
+        "white" | "w" => Ok(Player::White),
+        "black" | "b" => Ok(Player::Black),
+        _ => Err(anyhow!("The side must be 'white' or 'black'.")),
+    }
+}
+
+fn play(args: &[String]) -> Result<()> {
+    let (_, options) = parse_options(args, &["side", "depth", "fen", "load", "broadcast", "book", "ponder"])?;
+
+    let mut game = match (options.get("load"), options.get("fen")) {
+        (Some(_), Some(_)) => { return Err(anyhow!("Use either --load or --fen, not both.")); },
+        (Some(path), None) => Game::load(path)?,
+        (None, Some(fen)) => Game::new(Board::from_fen(fen)?),
+        (None, None) => Game::default(),
+    };
+
+    let mut render = RenderOptions::default();
+    if let Some(side) = options.get("side") {
+        let human = parse_side(side)?;
+        let engine_side = human.opponent();
+        let ponder = match options.get("ponder").map(String::as_str) {
+            None | Some("on") => true,
+            Some("off") => false,
+            Some(_) => { return Err(anyhow!("--ponder must be 'on' or 'off'.")); },
+        };
+        game.opponent = Some(Opponent { side: engine_side, depth: depth_option(&options, DEFAULT_DEPTH)?, ponder });
+        match engine_side {
+            Player::White => { game.white_name = "Computer".to_string(); },
+            Player::Black => { game.black_name = "Computer".to_string(); },
+        }
+        if let Player::Black = human {
+            render.orientation = Orientation::Black;
+        }
+    }
+
+    game.broadcast = broadcast_option(&options)?;
+    game.book = options.get("book").map(Book::open).transpose()?;
+
+    let mut result = LoopState::Continue;
+    while let LoopState::Continue = result {
+        result = chess::run(&mut game, &mut render);
+    }
+
+    Ok(())
+}
+
+/// Starts a spectator broadcast when `--broadcast PORT` is given.
+fn broadcast_option(options: &HashMap<String, String>) -> Result<Option<Broadcaster>> {
+    match options.get("broadcast") {
+        None => Ok(None),
+        Some(port) => {
+            let port = port.parse().map_err(|_| anyhow!("The broadcast port must be a number."))?;
+            Ok(Some(Broadcaster::start(port)?))
+        },
+    }
+}
+
+fn perft_command(args: &[String]) -> Result<()> {
+    let (positional, _) = parse_options(args, &[])?;
+    let depth: u32 = positional.first()
+        .ok_or_else(|| anyhow!("Usage: chess perft <depth> [FEN]"))?
+        .parse()
+        .map_err(|_| anyhow!("The depth must be a number."))?;
+    let board = match positional.len() {
+        1 => Board::new(),
+        _ => Board::from_fen(&positional[1..].join(" "))?,
+    };
+
+    let started = Instant::now();
+    let mut total = 0;
+    if depth == 0 {
+        total = 1;
+    } else {
+        for m in legal_moves(&board) {
+            let mut child = board.clone();
+            child.play(m)?;
+            child.next_turn();
+            let nodes = perft(&child, depth - 1);
+            println!("{}: {}", to_uci(&m), nodes);
+            total += nodes;
+        }
+    }
+
+    println!("\nNodes searched: {}", total);
+    println!("Time: {:.3}s", started.elapsed().as_secs_f64());
+    Ok(())
+}
+
+fn analyze(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["depth", "book", "multipv", "threads"])?;
+    if positional.is_empty() {
+        return Err(anyhow!("Usage: chess analyze <FEN> [--depth N] [--book FILE] [--multipv N] [--threads N]"));
+    }
+    let board = Board::from_fen(&positional.join(" "))?;
+    let depth = depth_option(&options, DEFAULT_DEPTH + 1)?;
+
+    board.print_with(&RenderOptions { compact: true, move_list: false, ..RenderOptions::default() });
+    println!("FEN: {}", board.to_fen());
+    if let Some(opening) = eco::lookup(&board) {
+        println!("Opening: {} {}", opening.code, opening.name);
+    }
+    println!("Static evaluation: {}", engine::format_score(evaluate_white(&board)));
+    print_pawns(&pawn_structure(&board));
+    for finding in tactics::find(&board) {
+        let side = match finding.player { Player::White => "White", Player::Black => "Black" };
+        println!("Tactics ({}): {}.", side, finding.describe(&board));
+    }
+    if let Some(path) = options.get("book") {
+        print_book_moves(&Book::open(path)?, &board);
+    }
+
+    if options.contains_key("multipv") || options.contains_key("threads") {
+        let lines: usize = match options.get("multipv") {
+            None => 1,
+            Some(lines) => lines.parse().map_err(|_| anyhow!("--multipv takes a number of lines"))?,
+        };
+        let threads: usize = match options.get("threads") {
+            None => 1,
+            Some(threads) => threads.parse().map_err(|_| anyhow!("--threads takes a number of threads"))?,
+        };
+        let started = Instant::now();
+        let analysis = engine::search_lines(&board, engine::Limits::depth(depth), lines, threads, &TranspositionTable::default(), |_| { });
+        let elapsed = started.elapsed();
+        if analysis.lines.is_empty() {
+            println!("No legal moves.");
+        }
+        for (i, line) in analysis.lines.iter().enumerate() {
+            let score = match board.get_turn() {
+                Player::White => line.score,
+                Player::Black => -line.score,
+            };
+            println!("{}. {} {}", i + 1, engine::format_score(score), line_to_san(&board, &line.pv));
+        }
+        println!("Depth {}, {} nodes in {:.3}s", depth, analysis.nodes, elapsed.as_secs_f64());
+        return Ok(());
+    }
+
+    let started = Instant::now();
+    let result = engine::search(&board, depth);
+    let elapsed = started.elapsed();
+
+    match result.best_move {
+        None => println!("No legal moves."),
+        Some(m) => {
+            let score = match board.get_turn() {
+                Player::White => result.score,
+                Player::Black => -result.score,
+            };
+            println!("Best move: {}", to_san(&board, &m));
+            println!("Score (White's view): {}", engine::format_score(score));
+            println!("Line: {}", line_to_san(&board, &result.pv));
+        },
+    }
+    println!("Depth {}, {} nodes in {:.3}s", depth, result.nodes, elapsed.as_secs_f64());
+
+    Ok(())
+}
+
+fn convert(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["to"])?;
+    if positional.is_empty() {
+        return Err(anyhow!("Usage: chess convert <FILE|FEN> [--to fen|pgn]"));
+    }
+    let joined = positional.join(" ");
+    let text = if Path::new(&joined).is_file() {
+        fs::read_to_string(&joined).with_context(|| format!("Could not read {}", joined))?
+    } else {
+        joined
+    };
+    let to = options.get("to").map(|t| t.to_lowercase());
+
+    if let Ok(board) = Board::from_fen(text.trim()) {
+        match to.as_deref() {
+            None | Some("pgn") => print!("{}", PgnGame::new(board).to_pgn()),
+            Some("fen") => println!("{}", board.to_fen()),
+            Some(other) => { return Err(anyhow!("Unknown format '{}'.", other)); },
+        }
+        return Ok(());
+    }
+
+    let games = parse_pgn(&text)?;
+    if games.is_empty() {
+        return Err(anyhow!("The input is neither a FEN nor a PGN."));
+    }
+    for (i, game) in games.iter().enumerate() {
+        match to.as_deref() {
+            None | Some("fen") => println!("{}", game.board.to_fen()),
+            Some("pgn") => {
+                if i > 0 { println!(); }
+                print!("{}", game.to_pgn());
+            },
+            Some(other) => { return Err(anyhow!("Unknown format '{}'.", other)); },
+        }
+    }
+
+    Ok(())
+}
+
+fn bench(args: &[String]) -> Result<()> {
+    let (_, options) = parse_options(args, &["depth"])?;
+    let depth = depth_option(&options, DEFAULT_DEPTH)?;
+
+    let started = Instant::now();
+    let mut total = 0;
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_fen(fen)?;
+        let result = engine::search(&board, depth);
+        let best = result.best_move.map(|m| to_san(&board, &m)).unwrap_or_else(|| "-".to_string());
+        println!("{:>10} nodes  {:<8} {}", result.nodes, best, fen);
+        total += result.nodes;
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+
+    println!("\nDepth: {}", depth);
+    println!("Nodes: {}", total);
+    println!("Time: {:.3}s", elapsed);
+    println!("Nodes/second: {:.0}", total as f64 / elapsed.max(0.001));
+    Ok(())
+}
+
+fn host(args: &[String]) -> Result<()> {
+    let (_, options) = parse_options(args, &["port", "side", "name", "broadcast"])?;
+    let port = match options.get("port") {
+        None => net::DEFAULT_PORT,
+        Some(port) => port.parse().map_err(|_| anyhow!("The port must be a number."))?,
+    };
+    let side = match options.get("side") {
+        None => Player::White,
+        Some(side) => parse_side(side)?,
+    };
+    let name = options.get("name").map(|n| n.as_str()).unwrap_or("Host");
+    net::host(port, side, name, broadcast_option(&options)?)
+}
+
+fn join(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["name"])?;
+    let address = positional.first().ok_or_else(|| anyhow!("Usage: chess join <ADDRESS[:PORT]> [--name NAME]"))?;
+    let name = options.get("name").map(|n| n.as_str()).unwrap_or("Guest");
+    net::join(address, name)
+}
+
+fn watch(args: &[String]) -> Result<()> {
+    let (positional, _) = parse_options(args, &[])?;
+    let address = positional.first().ok_or_else(|| anyhow!("Usage: chess watch <ADDRESS[:PORT]>"))?;
+    broadcast::watch(address)
+}
+
+fn serve(args: &[String]) -> Result<()> {
+    let (_, options) = parse_options(args, &["port"])?;
+    let port = match options.get("port") {
+        None => DEFAULT_API_PORT,
+        Some(port) => port.parse::<u16>().map_err(|_| anyhow!("The port must be a number."))?,
+    };
+    let server = ApiServer::bind(&format!("127.0.0.1:{}", port))?;
+    println!("Serving the chess API on http://{}", server.local_addr()?);
+    server.serve()
+}
+
+fn correspondence_command(args: &[String]) -> Result<()> {
+    let usage = "Usage: chess correspondence new|show|move <FILE> ...";
+    let (positional, options) = parse_options(args, &["white", "black", "fen", "as"])?;
+    let (action, path) = match positional.as_slice() {
+        [action, path, ..] => (action.as_str(), path.as_str()),
+        _ => { return Err(anyhow!(usage)); },
+    };
+
+    let game = match action {
+        "new" => {
+            let board = match options.get("fen") {
+                Some(fen) => Board::from_fen(fen)?,
+                None => Board::new(),
+            };
+            let white = options.get("white").map(|n| n.as_str()).unwrap_or("White");
+            let black = options.get("black").map(|n| n.as_str()).unwrap_or("Black");
+            let game = correspondence::create(path, board, white, black)?;
+            println!("Started {}.", path);
+            game
+        },
+        "show" => correspondence::load(path)?,
+        "move" => {
+            let text = positional.get(2).ok_or_else(|| anyhow!("Usage: chess correspondence move <FILE> <MOVE> --as <white|black|NAME>"))?;
+            let who = options.get("as").ok_or_else(|| anyhow!("Say who is moving with --as white, --as black or --as <name>."))?;
+            let player = correspondence::player_for(&correspondence::load(path)?, who)?;
+            correspondence::submit(path, player, text)?
+        },
+        _ => { return Err(anyhow!(usage)); },
+    };
+
+    game.board.print_with(&RenderOptions { move_list: false, ..RenderOptions::default() });
+    for line in correspondence::history(&game)? {
+        println!("{}", line);
+    }
+    match (game.result(), game.board.get_turn()) {
+        ("*", Player::White) => println!("{} (White) to move.", game.tag("White").unwrap_or("White")),
+        ("*", Player::Black) => println!("{} (Black) to move.", game.tag("Black").unwrap_or("Black")),
+        (result, _) => println!("Game over: {}", result),
+    }
+    Ok(())
+}
+
+fn print_pawns(structure: &PawnStructure) {
+    let squares = |list: &[usize]| list.iter().map(|&s| square_name(s)).collect::<Vec<_>>().join(" ");
+    let files = |list: &[usize]| list.iter().map(|&f| ((b'a' + f as u8) as char).to_string()).collect::<Vec<_>>().join(" ");
+
+    for (player, name) in [(Player::White, "White"), (Player::Black, "Black")] {
+        let side = structure.side(player);
+        let mut parts = Vec::<String>::new();
+        for (label, list) in [
+            ("passed", &side.passed), ("candidate", &side.candidates), ("connected", &side.connected),
+            ("doubled", &side.doubled), ("isolated", &side.isolated), ("backward", &side.backward),
+        ] {
+            if !list.is_empty() {
+                parts.push(format!("{} {}", label, squares(list)));
+            }
+        }
+        parts.push(format!("{} island{}", side.islands.len(), if side.islands.len() == 1 { "" } else { "s" }));
+        if !side.half_open_files.is_empty() {
+            parts.push(format!("half-open files {}", files(&side.half_open_files)));
+        }
+        println!("Pawns ({}): {}", name, parts.join("; "));
+    }
+    if !structure.open_files.is_empty() {
+        println!("Open files: {}", files(&structure.open_files));
+    }
+}
+
+fn print_book_moves(book: &Book, board: &Board) {
+    let moves = book.moves(board);
+    if moves.is_empty() {
+        println!("Book: no moves for this position.");
+        return;
+    }
+    let total: u64 = moves.iter().map(|(_, w)| *w as u64).sum();
+    println!("Book:");
+    for (m, weight) in moves {
+        println!("  {:<8} {:>6} {:>5.1}%", to_san(board, &m), weight, weight as f64 * 100.0 / total.max(1) as f64);
+    }
+}
+
+fn book_command(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["output", "plies"])?;
+    match positional.first().map(|a| a.as_str()) {
+        Some("build") => {
+            let output = options.get("output").ok_or_else(|| anyhow!("Say where to write the book with --output FILE."))?;
+            let plies = match options.get("plies") {
+                None => 20,
+                Some(plies) => plies.parse().map_err(|_| anyhow!("The number of plies must be a number."))?,
+            };
+            if positional.len() < 2 {
+                return Err(anyhow!("Usage: chess book build <PGN>... --output FILE [--plies N]"));
+            }
+
+            let mut games = Vec::<PgnGame>::new();
+            for path in &positional[1..] {
+                let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path))?;
+                games.extend(parse_pgn(&text).with_context(|| format!("In {}", path))?);
+            }
+            let book = Book::from_games(&games, plies)?;
+            book.save(output)?;
+            println!("Wrote {} entries from {} games to {}.", book.len(), games.len(), output);
+            Ok(())
+        },
+        Some("show") => {
+            let path = positional.get(1).ok_or_else(|| anyhow!("Usage: chess book show <FILE> [FEN]"))?;
+            let board = match positional.len() {
+                2 => Board::new(),
+                _ => Board::from_fen(&positional[2..].join(" "))?,
+            };
+            let book = Book::open(path)?;
+            println!("{} entries. Key {:016x}", book.len(), chess::zobrist::polyglot_key(&board));
+            print_book_moves(&book, &board);
+            Ok(())
+        },
+        _ => Err(anyhow!("Usage: chess book build|show ...")),
+    }
+}
+
+fn dtm_command(args: &[String]) -> Result<()> {
+    let (positional, _) = parse_options(args, &[])?;
+    match positional.first().map(|a| a.as_str()) {
+        Some("build") => {
+            let dir = positional.get(1).ok_or_else(|| anyhow!("Usage: chess dtm build <DIR> [KQK|KRK|KPK|KBNK]..."))?;
+            let wanted = match positional.len() {
+                2 => dtm::ENDINGS.to_vec(),
+                _ => positional[2..].iter().map(|name| Ending::from_name(name)).collect::<Result<Vec<_>>>()?,
+            };
+            fs::create_dir_all(dir).with_context(|| format!("Could not create {}", dir))?;
+
+            // Endings come in an order where each one's promotions are built before it.
+            let mut built = Vec::<DtmTable>::new();
+            for ending in dtm::ENDINGS {
+                let needed = wanted.contains(&ending) || wanted.iter().any(|w| w.needs().contains(&ending));
+                if !needed { continue; }
+                let path = Path::new(dir).join(format!("{}.dtm", ending.name()));
+                if !wanted.contains(&ending) && path.is_file() {
+                    built.push(DtmTable::open(&path)?);
+                    continue;
+                }
+
+                let started = Instant::now();
+                let table = DtmTable::generate(ending, &built)?;
+                table.save(&path)?;
+                let (won, drawn, lost) = table.counts();
+                println!("{}: {} won, {} drawn, {} lost for the side to move, in {:.1}s.", ending.name(), won, drawn, lost, started.elapsed().as_secs_f64());
+                if let Some((dtm, fen)) = table.longest_win() {
+                    println!("  Longest mate: {} moves, {}", dtm.moves(), fen);
+                }
+                built.push(table);
+            }
+            Ok(())
+        },
+        Some("probe") => {
+            if positional.len() < 3 {
+                return Err(anyhow!("Usage: chess dtm probe <DIR> <FEN>"));
+            }
+            let tables = DtmTables::open(&positional[1])?;
+            let board = Board::from_fen(&positional[2..].join(" "))?;
+            match tables.best_move(&board) {
+                None => match tables.probe(&board) {
+                    Some(dtm) => println!("{}", describe_dtm(dtm)),
+                    None => println!("No table for this position."),
+                },
+                Some((m, dtm)) => println!("{}; best move {}", describe_dtm(dtm), to_san(&board, &m)),
+            }
+            Ok(())
+        },
+        _ => Err(anyhow!("Usage: chess dtm build|probe ...")),
+    }
+}
+
+fn describe_dtm(dtm: Dtm) -> String {
+    match dtm {
+        Dtm::Win(_) => format!("Mate in {}", dtm.moves()),
+        Dtm::Loss(0) => "Checkmated".to_string(),
+        Dtm::Loss(_) => format!("Mated in {}", dtm.moves()),
+        Dtm::Draw => "Draw".to_string(),
+    }
+}
+
+fn puzzle_command(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["id", "theme", "history"])?;
+    let history_path = Path::new(options.get("history").map(|s| s.as_str()).unwrap_or(puzzle::HISTORY_FILE));
+
+    match positional.first().map(|a| a.as_str()) {
+        Some("stats") => {
+            let history = History::load(history_path)?;
+            if history.attempts.is_empty() {
+                println!("No puzzles tried yet.");
+                return Ok(());
+            }
+            let tried = history.attempts.len();
+            println!("{} of {} solved ({}%). Rating {}.", history.solved(), tried, history.solved() * 100 / tried, history.rating());
+            let best = history.attempts.iter().map(|attempt| attempt.rating).max().unwrap_or(0);
+            println!("Best rating {}.", best);
+            for attempt in history.attempts.iter().rev().take(10) {
+                println!("  {} {:<8} {:<6} puzzle {:>4}  rating {:>4}", attempt.time, attempt.id, if attempt.solved { "solved" } else { "failed" }, attempt.puzzle_rating, attempt.rating);
+            }
+            Ok(())
+        },
+        Some(path) => {
+            let mut puzzles = puzzle::load(path)?;
+            if let Some(id) = options.get("id") {
+                puzzles.retain(|p| &p.id == id);
+                if puzzles.is_empty() {
+                    return Err(anyhow!("There is no puzzle {} in {}.", id, path));
+                }
+            }
+            if let Some(theme) = options.get("theme") {
+                puzzles.retain(|p| p.themes.iter().any(|t| t.eq_ignore_ascii_case(theme)));
+            }
+            puzzle::train(&puzzles, history_path)
+        },
+        None => Err(anyhow!("Usage: chess puzzle <CSV> [--id ID] [--theme THEME] [--history FILE] | chess puzzle stats")),
+    }
+}
+
+fn review_command(args: &[String]) -> Result<()> {
+    let (positional, options) = parse_options(args, &["depth", "output"])?;
+    let path = positional.first().ok_or_else(|| anyhow!("Usage: chess review <PGN> [--depth N] [--output FILE]"))?;
+    let depth = depth_option(&options, DEFAULT_DEPTH)?;
+    let text = fs::read_to_string(path).with_context(|| format!("Could not read {}", path))?;
+    let mut games = parse_pgn(&text)?;
+
+    for (i, game) in games.iter_mut().enumerate() {
+        if i > 0 { println!(); }
+        let white = game.tag("White").unwrap_or("White").to_string();
+        let black = game.tag("Black").unwrap_or("Black").to_string();
+        println!("{} - {}  {}", white, black, game.result());
+
+        let result = review::review(&game.board, depth)?;
+        let mut replay = Board::from_fen(game.board.get_start_fen())?;
+        for (reviewed, m) in result.moves.iter().zip(game.board.get_history()) {
+            if reviewed.class >= Class::Inaccuracy {
+                let number = match replay.get_turn() {
+                    Player::White => format!("{}.", replay.get_fullmove_number()),
+                    Player::Black => format!("{}...", replay.get_fullmove_number()),
+                };
+                let best = reviewed.best.as_deref().map(|best| format!(", best {}", best)).unwrap_or_default();
+                println!(
+                    "  {:>5} {:<7} {:<10} {} -> {}{}",
+                    number, reviewed.san, reviewed.class.name(),
+                    engine::format_score(reviewed.eval_before), engine::format_score(reviewed.eval_after), best,
+                );
+            }
+            replay.play(*m)?;
+            replay.next_turn();
+        }
+        for (name, summary) in [(&white, &result.white), (&black, &result.black)] {
+            println!(
+                "  {}: accuracy {:.1}%, average loss {:.0}, {} inaccuracies, {} mistakes, {} blunders",
+                name, summary.accuracy, summary.average_loss, summary.inaccuracies, summary.mistakes, summary.blunders,
+            );
+        }
+        review::annotate(game, &result);
+    }
+
+    if let Some(output) = options.get("output") {
+        let text: Vec<String> = games.iter().map(|game| game.to_pgn()).collect();
+        fs::write(output, text.join("\n")).with_context(|| format!("Could not write {}", output))?;
+        println!("Annotated games written to {}.", output);
+    }
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::{
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+use chess::chess::api::ApiServer;
+
+/// Starts a server on a free port of localhost.
+fn start() -> SocketAddr {
+    let server = ApiServer::bind("127.0.0.1:0").unwrap();
+    let address = server.local_addr().unwrap();
+    server.spawn();
+    address
+}
+
+/// Sends one request and gives the status and body of the answer.
+fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}", method, path, body.len(), body).unwrap();
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+
+    let status = answer.split_whitespace().nth(1).unwrap().parse().unwrap();
+    let body = answer.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[test]
+fn create_play_and_undo() {
+    let address = start();
+
+    let (status, body) = request(address, "POST", "/games", "");
+    assert_eq!(status, 201);
+    assert!(body.starts_with("{\"id\":1,"));
+    assert!(body.contains("\"turn\":\"white\""));
+
+    let (status, body) = request(address, "GET", "/games/1/moves", "");
+    assert_eq!(status, 200);
+    assert_eq!(body.matches("\"uci\"").count(), 20);
+    assert!(body.contains("{\"uci\":\"g1f3\",\"san\":\"Nf3\"}"));
+
+    let (status, body) = request(address, "POST", "/games/1/moves", "{\"move\": \"e4\"}");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"uci\":\"e2e4\""));
+    let (status, body) = request(address, "POST", "/games/1/moves", "{\"move\": \"e7e5\"}");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"san\":\"e5\""));
+
+    let (status, body) = request(address, "GET", "/games/1/fen", "");
+    assert_eq!(status, 200);
+    assert_eq!(body, "{\"fen\":\"rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2\"}");
+
+    let (status, body) = request(address, "GET", "/games/1/pgn", "");
+    assert_eq!(status, 200);
+    assert!(body.contains("1. e4 e5"));
+
+    let (status, body) = request(address, "POST", "/games/1/undo", "");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"moves\":[\"e4\"]"));
+    assert!(body.contains("\"turn\":\"black\""));
+
+    let (status, _) = request(address, "DELETE", "/games/1", "");
+    assert_eq!(status, 200);
+    let (status, _) = request(address, "GET", "/games/1", "");
+    assert_eq!(status, 404);
+}
+
+#[test]
+fn bad_requests_leave_the_game_alone() {
+    let address = start();
+    request(address, "POST", "/games", "{\"fen\": \"4k3/8/8/8/8/8/4Q3/4K3 b - - 0 1\"}");
+
+    let (status, _) = request(address, "POST", "/games/1/moves", "{\"move\": \"e8e7\"}");
+    assert_eq!(status, 400);
+    let (status, _) = request(address, "POST", "/games/1/moves", "{\"move\": \"Qe2\"}");
+    assert_eq!(status, 400);
+    let (status, _) = request(address, "POST", "/games/1/undo", "");
+    assert_eq!(status, 400);
+
+    // The side not to move in check: the king could be taken, so the position is refused.
+    let (status, body) = request(address, "POST", "/games", "{\"fen\": \"4k3/8/8/8/8/8/4Q3/4K3 w - - 0 1\"}");
+    assert_eq!(status, 400);
+    assert!(body.contains("in check"));
+
+    let (status, body) = request(address, "POST", "/games/1/moves", "{\"move\": \"Kd8\"}");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"fen\":\"3k4/8/8/8/8/8/4Q3/4K3 w - - 1 2\""));
+    let (status, body) = request(address, "GET", "/games", "");
+    assert_eq!(status, 200);
+    assert_eq!(body, "{\"games\":[1]}");
+}
+
+#[test]
+fn slow_requests_time_out() {
+    let server = ApiServer::bind("127.0.0.1:0").unwrap().read_timeout(Duration::from_millis(200));
+    let address = server.local_addr().unwrap();
+    server.spawn();
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(stream, "POST /games HTTP/1.1\r\nContent-Length: 10\r\n\r\n{{").unwrap();
+    let mut answer = String::new();
+    stream.read_to_string(&mut answer).unwrap();
+    assert!(answer.starts_with("HTTP/1.1 408 "));
+}
@@ -0,0 +1,60 @@
+#![cfg(feature = "serde")]
+
+use chess::chess::{
+    board::Board,
+    interpret::parse_uci,
+    utils::{Move, Piece, PieceType, Player, PlayerPiece},
+};
+
+fn play(board: &mut Board, moves: &[&str]) {
+    for text in moves {
+        let m = parse_uci(text, board).unwrap();
+        board.play(m).unwrap();
+        board.next_turn();
+    }
+}
+
+#[test]
+fn board_round_trip_keeps_the_history() {
+    let mut board = Board::new();
+    // Castling short and a capture en passant, the moves the UCI form has to get right.
+    play(&mut board, &["e2e4", "g8f6", "e4e5", "d7d5", "e5d6", "e7d6", "g1f3", "f8e7", "f1e2", "e8g8", "e1g1"]);
+
+    let json = serde_json::to_string(&board).unwrap();
+    assert!(json.contains("\"moves\":[\"e2e4\",\"g8f6\",\"e4e5\",\"d7d5\",\"e5d6\",\"e7d6\",\"g1f3\",\"f8e7\",\"f1e2\",\"e8g8\",\"e1g1\"]"));
+
+    let read: Board = serde_json::from_str(&json).unwrap();
+    assert_eq!(read.to_fen(), board.to_fen());
+    assert_eq!(read.get_start_fen(), board.get_start_fen());
+    assert_eq!(read.get_history(), board.get_history());
+    assert_eq!(read.san_history(), board.san_history());
+}
+
+#[test]
+fn board_from_a_fen_string() {
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    let board: Board = serde_json::from_str(&format!("\"{}\"", fen)).unwrap();
+    assert_eq!(board.to_fen(), fen);
+
+    let read: Board = serde_json::from_str(&serde_json::to_string(&board).unwrap()).unwrap();
+    assert_eq!(read.to_fen(), fen);
+}
+
+#[test]
+fn board_with_moves_that_do_not_reach_its_fen_is_refused() {
+    let json = r#"{"fen": "8/8/8/8/8/8/8/8 w - - 0 1", "start": "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "moves": ["e2e4"]}"#;
+    assert!(serde_json::from_str::<Board>(json).is_err());
+}
+
+#[test]
+fn moves_and_pieces_round_trip() {
+    for m in [Move::new(52, 36), Move::promote(12, 4, PieceType::Queen)] {
+        let json = serde_json::to_string(&m).unwrap();
+        assert_eq!(serde_json::from_str::<Move>(&json).unwrap(), m);
+    }
+    assert_eq!(serde_json::to_string(&Move::new(52, 36)).unwrap(), "\"e2e4\"");
+
+    let pieces = [Piece::None, Piece::Piece(PlayerPiece { piece: PieceType::Knight, player: Player::Black })];
+    let read: Vec<Piece> = serde_json::from_str(&serde_json::to_string(&pieces).unwrap()).unwrap();
+    assert_eq!(read, pieces);
+}
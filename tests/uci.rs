@@ -0,0 +1,61 @@
+use std::{
+    io::{Cursor, Write},
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use chess::chess::uci::Uci;
+
+/// Collects what the engine writes, for the test to read afterwards.
+#[derive(Clone, Default)]
+struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs the engine on the commands and gives its output, failing if it doesn't finish in time.
+fn run(commands: &str) -> String {
+    let output = Output::default();
+    let (sender, receiver) = mpsc::channel();
+    let (input, engine_output) = (commands.to_string(), output.clone());
+    thread::spawn(move || {
+        let result = Uci::default().run(Cursor::new(input), engine_output);
+        sender.send(result.is_ok()).unwrap();
+    });
+    let finished = receiver.recv_timeout(Duration::from_secs(60)).expect("the engine hung");
+    assert!(finished);
+    let written = output.0.lock().unwrap().clone();
+    String::from_utf8(written).unwrap()
+}
+
+#[test]
+fn go_depth() {
+    let output = run("uci\nisready\nposition startpos moves e2e4\ngo depth 2\n");
+    assert!(output.contains("uciok\nreadyok\n"));
+    assert!(output.contains("info depth 2 "));
+    assert_eq!(output.matches("bestmove ").count(), 1);
+}
+
+#[test]
+fn commands_during_an_unbounded_search_stop_it() {
+    let output = run("position startpos\ngo infinite\nsetoption name Threads value 2\ngo depth 1\nquit\n");
+    assert_eq!(output.matches("bestmove ").count(), 2);
+
+    let output = run("position startpos moves e2e4\ngo ponder wtime 1000 btime 1000\ngo depth 1\n");
+    assert_eq!(output.matches("bestmove ").count(), 2);
+}
+
+#[test]
+fn lines_cut_short_by_the_table_are_filled_back_in() {
+    let output = run("setoption name MultiPV value 3\nposition fen 6k1/5ppp/8/8/8/8/5PPP/R3R1K1 w - - 0 1\ngo depth 5\n");
+    let third = output.lines().find(|line| line.starts_with("info depth 5 multipv 3 ")).unwrap();
+    let pv = third.split(" pv ").nth(1).unwrap();
+    assert!(pv.split_whitespace().count() >= 4, "{}", third);
+}